@@ -1,12 +1,11 @@
-use super::create_tx;
-use crate::builder::genesis::get_account_id_from_seed;
-use crate::executor::ClientInMem;
-use crate::primitives::runtime::{Balance, BlockId, RuntimeCall};
-use crate::primitives::{ExtrinsicSigner, RawExtrinsic, SpecAccountSeed, SpecChainSpec};
+use super::create_signed_tx;
+use crate::primitives::runtime::{AccountId, Balance, RuntimeCall};
+use crate::primitives::{
+    ExtrinsicOutput, SpecAccountSeed, SpecAddress, SpecBalance, SpecGenesisSource, SpecTxOptions,
+};
 use crate::Result;
 use pallet_balances::Call as BalancesCall;
-use sp_core::crypto::Pair;
-use std::convert::TryInto;
+use std::convert::TryFrom;
 use std::str::FromStr;
 use structopt::StructOpt;
 
@@ -38,52 +37,396 @@ module!(
     enum CallCmd {
         #[serde(rename = "transfer")]
         Transfer {
+            #[structopt(flatten)]
+            #[serde(flatten)]
+            genesis: SpecGenesisSource,
             #[structopt(short, long)]
-            genesis: Option<SpecChainSpec>,
+            from: SpecAccountSeed,
+            #[structopt(short, long)]
+            to: SpecAddress,
+            #[structopt(short, long)]
+            balance: SpecBalance,
+            #[structopt(flatten)]
+            #[serde(flatten)]
+            options: SpecTxOptions,
+        },
+        #[serde(rename = "transfer_keep_alive")]
+        TransferKeepAlive {
+            #[structopt(flatten)]
+            #[serde(flatten)]
+            genesis: SpecGenesisSource,
+            #[structopt(short, long)]
+            from: SpecAccountSeed,
+            #[structopt(short, long)]
+            to: SpecAddress,
+            #[structopt(short, long)]
+            balance: SpecBalance,
+            #[structopt(flatten)]
+            #[serde(flatten)]
+            options: SpecTxOptions,
+        },
+        #[serde(rename = "set_balance")]
+        SetBalance {
+            #[structopt(flatten)]
+            #[serde(flatten)]
+            genesis: SpecGenesisSource,
+            #[structopt(short, long)]
+            from: SpecAccountSeed,
+            #[structopt(short, long)]
+            who: SpecAddress,
+            #[structopt(long)]
+            new_free: SpecBalance,
+            #[structopt(long)]
+            #[serde(default)]
+            new_reserved: SpecBalance,
+            #[structopt(flatten)]
+            #[serde(flatten)]
+            options: SpecTxOptions,
+        },
+        #[serde(rename = "force_transfer")]
+        ForceTransfer {
+            #[structopt(flatten)]
+            #[serde(flatten)]
+            genesis: SpecGenesisSource,
             #[structopt(short, long)]
             from: SpecAccountSeed,
             #[structopt(short, long)]
-            to: SpecAccountSeed,
+            source: SpecAddress,
+            #[structopt(short, long)]
+            to: SpecAddress,
             #[structopt(short, long)]
-            balance: u64,
+            balance: SpecBalance,
+            #[structopt(flatten)]
+            #[serde(flatten)]
+            options: SpecTxOptions,
         },
     }
 
     impl PalletBalancesCmd {
-        fn run(self) -> Result<RawExtrinsic> {
-            match self.call {
+        fn run(self, session) -> Result<ExtrinsicOutput> {
+            let (genesis, from, options) = match &self.call {
                 CallCmd::Transfer {
                     genesis,
                     from,
-                    to,
-                    balance,
-                } => {
-                    let client = if let Some(chain_spec) = genesis {
-                        ClientInMem::new_with_genesis(chain_spec.try_into()?)
-                    } else {
-                        ClientInMem::new()
-                    }?;
-
-                    client
-                        .exec_context(&BlockId::Number(0), || {
-                            create_tx::<ExtrinsicSigner>(
-                                from.try_into()?,
-                                RuntimeCall::Balances(BalancesCall::transfer(
-                                    get_account_id_from_seed::<<ExtrinsicSigner as Pair>::Public>(
-                                        to.as_str(),
-                                    )
-                                    .into(),
-                                    balance as Balance,
-                                )),
-                                0,
-                            )
-                            .map(|t| RawExtrinsic::from(t))
-                            .map(Some)
-                        })
-                        // Is always `Some` in this case.
-                        .map(|extr| extr.unwrap())
+                    options,
+                    ..
                 }
-            }
+                | CallCmd::TransferKeepAlive {
+                    genesis,
+                    from,
+                    options,
+                    ..
+                }
+                | CallCmd::SetBalance {
+                    genesis,
+                    from,
+                    options,
+                    ..
+                }
+                | CallCmd::ForceTransfer {
+                    genesis,
+                    from,
+                    options,
+                    ..
+                } => (genesis.clone(), from.clone(), options.clone()),
+            };
+
+            create_signed_tx(
+                session.client(genesis.load()?)?,
+                from,
+                NestedCall::from(self.call).into_call()?,
+                options,
+            )
         }
     }
 );
+
+// The call as nested into another call, such as `pallet_sudo::sudo`, without
+// any fields related to signing.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum NestedCall {
+    #[serde(rename = "transfer")]
+    Transfer {
+        to: SpecAddress,
+        balance: SpecBalance,
+    },
+    #[serde(rename = "transfer_keep_alive")]
+    TransferKeepAlive {
+        to: SpecAddress,
+        balance: SpecBalance,
+    },
+    #[serde(rename = "set_balance")]
+    SetBalance {
+        who: SpecAddress,
+        new_free: SpecBalance,
+        #[serde(default)]
+        new_reserved: SpecBalance,
+    },
+    #[serde(rename = "force_transfer")]
+    ForceTransfer {
+        source: SpecAddress,
+        to: SpecAddress,
+        balance: SpecBalance,
+    },
+}
+
+impl From<CallCmd> for NestedCall {
+    fn from(value: CallCmd) -> Self {
+        match value {
+            CallCmd::Transfer { to, balance, .. } => NestedCall::Transfer {
+                to: to,
+                balance: balance,
+            },
+            CallCmd::TransferKeepAlive { to, balance, .. } => NestedCall::TransferKeepAlive {
+                to: to,
+                balance: balance,
+            },
+            CallCmd::SetBalance {
+                who,
+                new_free,
+                new_reserved,
+                ..
+            } => NestedCall::SetBalance {
+                who: who,
+                new_free: new_free,
+                new_reserved: new_reserved,
+            },
+            CallCmd::ForceTransfer {
+                source,
+                to,
+                balance,
+                ..
+            } => NestedCall::ForceTransfer {
+                source: source,
+                to: to,
+                balance: balance,
+            },
+        }
+    }
+}
+
+impl NestedCall {
+    pub fn into_call(self) -> Result<RuntimeCall> {
+        Ok(RuntimeCall::Balances(match self {
+            NestedCall::Transfer { to, balance } => {
+                BalancesCall::transfer(AccountId::try_from(to)?, Balance::try_from(balance)?)
+            }
+            NestedCall::TransferKeepAlive { to, balance } => BalancesCall::transfer_keep_alive(
+                AccountId::try_from(to)?,
+                Balance::try_from(balance)?,
+            ),
+            NestedCall::SetBalance {
+                who,
+                new_free,
+                new_reserved,
+            } => BalancesCall::set_balance(
+                AccountId::try_from(who)?,
+                Balance::try_from(new_free)?,
+                Balance::try_from(new_reserved)?,
+            ),
+            NestedCall::ForceTransfer {
+                source,
+                to,
+                balance,
+            } => BalancesCall::force_transfer(
+                AccountId::try_from(source)?,
+                AccountId::try_from(to)?,
+                Balance::try_from(balance)?,
+            ),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::blocks::{BlockCmd, BlockCmdResult};
+    use crate::builder::test_utils::{balances_extrinsic, build_block_in, run_call};
+    use crate::executor::Session;
+    use crate::primitives::runtime::{Block, UncheckedExtrinsic};
+    use crate::primitives::{SpecApplyResult, SpecMutation};
+    use codec::{Compact, Decode, Encode};
+    use sp_core::H256;
+    use sp_runtime::generic::{Era, SignedPayload};
+    use sp_runtime::traits::Verify;
+
+    #[test]
+    fn nonce_tracking() {
+        let mut session = Session::new();
+
+        let mut transfer = |yaml: &str| {
+            let extr = match run_call::<PalletBalancesCmd>(&mut session, yaml).unwrap() {
+                ExtrinsicOutput::Valid(extr) => extr,
+                _ => panic!("Expected a valid extrinsic"),
+            };
+            let (_, _, extra) = UncheckedExtrinsic::try_from(extr)
+                .unwrap()
+                .signature
+                .unwrap();
+            // `CheckNonce` signed extension.
+            Compact::<u32>::decode(&mut extra.4.encode().as_slice())
+                .unwrap()
+                .0
+        };
+
+        let from_alice = "transfer: { from: alice, to: bob, balance: 100 }";
+        let from_bob = "transfer: { from: bob, to: alice, balance: 100 }";
+
+        assert_eq!(transfer(from_alice), 0);
+        assert_eq!(transfer(from_alice), 1);
+        assert_eq!(transfer(from_bob), 0);
+        assert_eq!(
+            transfer("transfer: { from: alice, to: bob, balance: 100, nonce: 5 }"),
+            5
+        );
+        assert_eq!(transfer(from_alice), 6);
+    }
+
+    #[test]
+    fn signed_extra() {
+        let mut session = Session::new();
+
+        let mut transfer =
+            |yaml: &str| match run_call::<PalletBalancesCmd>(&mut session, yaml).unwrap() {
+                ExtrinsicOutput::Valid(extr) => UncheckedExtrinsic::try_from(extr).unwrap(),
+                _ => panic!("Expected a valid extrinsic"),
+            };
+
+        let mortal = transfer(
+            "transfer: { from: alice, to: bob, balance: 100, era: { mortal: { period: 64 } }, tip: 10 }",
+        );
+        let other_runtime = transfer(
+            r#"
+            transfer:
+              from: alice
+              to: bob
+              balance: 100
+              era: immortal
+              spec_version: 2
+              tx_version: 2
+              genesis_hash: "0x4545454545454545454545454545454545454545454545454545454545454545"
+        "#,
+        );
+        let genesis_hash = session.client(None).unwrap().block_hash(0).unwrap();
+
+        // `CheckEra` and `ChargeTransactionPayment` signed extensions. The era
+        // is born at the best block.
+        let (_, _, extra) = mortal.signature.unwrap();
+        assert_eq!(extra.3.encode(), Era::mortal(64, 0).encode());
+        assert_eq!(extra.6.encode(), Compact(10 as Balance).encode());
+
+        let (signer, signature, extra) = other_runtime.signature.unwrap();
+        assert_eq!(extra.3.encode(), Era::Immortal.encode());
+        assert_eq!(extra.6.encode(), Compact(0 as Balance).encode());
+
+        // The versions and the genesis hash are not part of the extrinsic, but
+        // of the signed payload.
+        let payload = SignedPayload::from_raw(
+            other_runtime.function,
+            extra,
+            (
+                2,
+                2,
+                H256::repeat_byte(0x45),
+                // Birth hash of the immortal era.
+                genesis_hash,
+                (),
+                (),
+                (),
+            ),
+        );
+        assert!(payload.using_encoded(|payload| signature.verify(payload, &signer)));
+    }
+
+    #[test]
+    fn mutations() {
+        let mut session = Session::new();
+
+        // Gives alice an on-chain nonce and enough blocks for an expired era.
+        let extr = balances_extrinsic(
+            &mut session,
+            "transfer: { from: alice, to: bob, balance: 100 }",
+        );
+        build_block_in(
+            &mut session,
+            &format!(
+                "build: {{ header: {{ digest: {{ logs: [] }} }}, extrinsics: [{}] }}",
+                extr
+            ),
+        )
+        .unwrap();
+        run_call::<BlockCmd>(&mut session, "build_chain: { count: 3 }").unwrap();
+
+        for label in &[
+            "bad_signature",
+            "wrong_genesis_hash",
+            "stale_nonce",
+            "expired_era",
+            "wrong_spec_version",
+            "truncated_scale",
+            "oversized_length",
+        ] {
+            let (extr, mutation) = match run_call::<PalletBalancesCmd>(
+                &mut session,
+                &format!(
+                    "transfer: {{ from: alice, to: bob, balance: 100, mutate: {} }}",
+                    label
+                ),
+            )
+            .unwrap()
+            {
+                ExtrinsicOutput::Mutated {
+                    extrinsic,
+                    mutation,
+                } => (extrinsic, mutation),
+                _ => panic!("Expected a mutated extrinsic"),
+            };
+            assert_eq!(serde_json::to_value(mutation).unwrap(), *label);
+
+            let bytes = hex::decode(
+                serde_json::to_value(&extr)
+                    .unwrap()
+                    .as_str()
+                    .unwrap()
+                    .trim_start_matches("0x"),
+            )
+            .unwrap();
+            let built = run_call::<BlockCmd>(
+                &mut session,
+                &format!(
+                    "build: {{ header: {{ digest: {{ logs: [] }} }}, extrinsics: [{}], fail_on: never }}",
+                    serde_json::to_string(&extr).unwrap()
+                ),
+            );
+
+            match mutation {
+                SpecMutation::TruncatedScale => {
+                    assert!(UncheckedExtrinsic::decode(&mut bytes.as_slice()).is_err());
+                    assert!(built.is_err());
+                }
+                // The length prefix exceeds the remaining bytes, so it can't be
+                // decoded as opaque extrinsic. The runtime ignores the prefix
+                // though, so the transfer is still applied and included with
+                // a correct prefix.
+                SpecMutation::OversizedLength => {
+                    assert!(Vec::<u8>::decode(&mut bytes.as_slice()).is_err());
+                    let decoded = UncheckedExtrinsic::decode(&mut bytes.as_slice()).unwrap();
+
+                    let built = match built.unwrap() {
+                        BlockCmdResult::BuildBlock(built) => built,
+                        _ => panic!("Expected a single block"),
+                    };
+                    assert_eq!(built.apply_results[0], SpecApplyResult::Ok);
+                    assert_eq!(Block::try_from(built.block).unwrap().extrinsics[1], decoded);
+                }
+                _ => match built.unwrap() {
+                    BlockCmdResult::BuildBlock(built) => {
+                        assert!(built.apply_results[0].is_invalid(), "{}", label)
+                    }
+                    _ => panic!("Expected a single block"),
+                },
+            }
+        }
+    }
+}
@@ -1,20 +1,94 @@
+use super::create_signed_tx;
+use super::genesis::authority_pair_from_seed;
+use super::timestamp::{slot_timestamp, timestamp_inherent};
+use super::Decoded;
 use crate::executor::ClientInMem;
-use crate::primitives::runtime::{Block, BlockId, Timestamp};
-use crate::primitives::{RawBlock, SpecBlock};
+use crate::primitives::runtime::{
+    AccountId, Block, BlockId, BlockNumber, MaximumBlockLength, MaximumBlockWeight, MinimumPeriod,
+    RuntimeCall, UncheckedExtrinsic, SLOT_DURATION,
+};
+use crate::primitives::{
+    storage_prefix, ExtrinsicOutput, RawBlock, SpecAccountSeed, SpecApplyResult, SpecBlock,
+    SpecExtrinsicBatch, SpecFailOn, SpecGenesisSource, SpecHash, SpecHeader, SpecTxOptions,
+    SAMPLE_ACCOUNTS,
+};
 use crate::Result;
+use codec::{Decode, Encode};
+use frame_support::weights::Weight;
+use pallet_balances::Call as BalancesCall;
+use pallet_transaction_payment_rpc_runtime_api::TransactionPaymentApi;
+use serde_json::Value;
 use sp_api::Core;
 use sp_block_builder::BlockBuilder;
-use sp_inherents::InherentData;
-use sp_runtime::transaction_validity::TransactionValidityError;
-use std::convert::{TryFrom, TryInto};
+use sp_consensus_aura::sr25519::AuthorityPair as AuraPair;
+use sp_consensus_aura::{AuraApi, AURA_ENGINE_ID};
+use sp_core::{Pair, H256};
+use sp_runtime::generic::DigestItem;
+use sp_runtime::traits::Header as HeaderT;
+use std::convert::TryFrom;
 use std::mem::take;
 use structopt::StructOpt;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum BlockCmdResult {
-    BuildBlock(RawBlock),
-    ExecuteBlocks,
+    BuildBlock(BuiltBlock),
+    BuildChain(Vec<BuiltBlock>),
+    ExecuteBlocks(Vec<ExecutedBlock>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuiltBlock {
+    pub block: RawBlock,
+    // The apply result and the fee of each extrinsic, excluding inherents.
+    pub apply_results: Vec<SpecApplyResult>,
+    pub fees: Vec<ExtrinsicFee>,
+    pub usage: BlockUsage,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtrinsicFee {
+    pub weight: Weight,
+    pub class: String,
+    // Balances can exceed the number range of JSON.
+    pub partial_fee: String,
+}
+
+// How full the block is.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockUsage {
+    pub weight: Weight,
+    pub max_weight: Weight,
+    pub length: u32,
+    pub max_length: u32,
+}
+
+impl Decoded for BuiltBlock {
+    fn decoded(&self) -> Option<Value> {
+        self.block.decoded()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutedBlock {
+    pub hash: SpecHash,
+    pub number: BlockNumber,
+    // The state root of the header. Execution only succeeds if it matches the
+    // state after execution, so it is not computed separately.
+    pub state_root: SpecHash,
+    // Log statements of the runtime during execution.
+    pub logs: Vec<String>,
+}
+
+impl Decoded for BlockCmdResult {
+    fn decoded(&self) -> Option<Value> {
+        match self {
+            BlockCmdResult::BuildBlock(block) => block.decoded(),
+            BlockCmdResult::BuildChain(blocks) => blocks.decoded(),
+            // Already readable.
+            BlockCmdResult::ExecuteBlocks(_) => None,
+        }
+    }
 }
 
 module!(
@@ -28,91 +102,99 @@ module!(
             #[serde(flatten)]
             spec_block: SpecBlock,
         },
+        #[serde(rename = "build_chain")]
+        BuildChain {
+            #[structopt(flatten)]
+            #[serde(flatten)]
+            genesis: SpecGenesisSource,
+            #[structopt(short, long)]
+            parent_hash: Option<SpecHash>,
+            #[structopt(short, long)]
+            #[serde(default)]
+            extrinsics: Vec<SpecExtrinsicBatch>,
+            #[structopt(short, long)]
+            count: Option<usize>,
+            #[structopt(long, default_value = "dispatch_error")]
+            #[serde(default)]
+            fail_on: SpecFailOn,
+        },
         #[serde(rename = "execute")]
         ExecuteBlocks {
-            #[structopt(short, long)]
+            #[structopt(flatten)]
             #[serde(flatten)]
+            genesis: SpecGenesisSource,
+            #[structopt(short, long)]
             blocks: Vec<RawBlock>,
         },
     }
 
     impl BlockCmd {
-        fn run(self) -> Result<BlockCmdResult> {
+        fn run(self, session) -> Result<BlockCmdResult> {
             match self.call {
                 CallCmd::BuildBlock { mut spec_block } => {
-                    // Create the block by calling the runtime APIs.
-                    let client = if let Some(chain_spec) = take(&mut spec_block.genesis) {
-                        ClientInMem::new_with_genesis(chain_spec.try_into()?)
-                    } else {
-                        ClientInMem::new()
-                    }?;
-
-                    // Convert into runtime types.
-                    let (at, header, extrinsics) = spec_block.prep()?;
-
-                    let rt = client.runtime_api();
-
-                    rt.initialize_block(&at, &header).map_err(|err| {
-                        failure::err_msg(format!("Failed to initialize block: {}", err))
-                    })?;
-
-                    for extr in &extrinsics {
-                        let apply_result = rt.apply_extrinsic(&at, extr.clone()).map_err(|err| {
-                            failure::err_msg(format!("Failed to apply extrinsic: {}", err))
-                        })?;
-
-                        if let Err(validity) = apply_result {
-                            if validity.exhausted_resources() {
-                                return Err(failure::err_msg("Resources exhausted"));
-                            } else {
-                                return Err(failure::err_msg(format!("Invalid transaction: {}", <TransactionValidityError as Into<&'static str>>::into(validity))));
-                            }
-                        } else {
-                            return Err(failure::err_msg("Apply extrinsic dispatch error"));
-                        }
-                    }
+                    let client = session.client(take(&mut spec_block.genesis).load()?)?;
 
-                    // Create timestamp in an externalities-provided environment.
-                    let timestamp = client
-                        .exec_context(&at, || Ok(Some(Timestamp::now())))
-                        .unwrap()
-                        .unwrap();
-
-                    // Include inherent.
-                    let x = rt
-                        .inherent_extrinsics(&at, {
-                            let mut inherent = InherentData::new();
-                            inherent.put_data(*b"timstap0", &timestamp).map_err(|err| {
-                                failure::err_msg(format!("Failed to create inherent: {}", err))
-                            })?;
-                            inherent
-                        })
-                        .map_err(|err| {
-                            failure::err_msg(format!("Failed to include inherent: {}", err))
-                        })?;
-
-                    for e in x {
-                        let _ = rt.apply_extrinsic(&at, e).map_err(|err| {
-                            failure::err_msg(format!("Failed to apply extrinsic: {}", err))
-                        })?;
-                    }
+                    let (block, apply_results, fees) = build_block(client, spec_block)?;
 
-                    let header = rt
-                        .finalize_block(&at)
-                        .map_err(|_| failure::err_msg("Failed to finalize block"))?;
+                    Ok(BlockCmdResult::BuildBlock(import_built_block(
+                        client,
+                        block,
+                        apply_results,
+                        fees,
+                    )?))
+                }
+                CallCmd::BuildChain {
+                    genesis,
+                    parent_hash,
+                    extrinsics,
+                    count,
+                    fail_on,
+                } => {
+                    let client = session.client(genesis.load()?)?;
 
-                    Ok(BlockCmdResult::BuildBlock(
-                        Block {
-                            header: header,
-                            extrinsics: extrinsics,
+                    // Either build blocks for each extrinsic batch or the
+                    // specified amount of empty blocks.
+                    let batches = match count {
+                        Some(_) if !extrinsics.is_empty() => {
+                            return Err(failure::err_msg(
+                                "Either extrinsics or a block count can be specified, not both",
+                            ))
                         }
-                        .into(),
-                    ))
+                        Some(count) => vec![Default::default(); count],
+                        None => extrinsics,
+                    };
+
+                    let mut parent_hash = parent_hash;
+                    let mut chain = vec![];
+
+                    for batch in batches {
+                        let (block, apply_results, fees) = build_block(
+                            client,
+                            SpecBlock {
+                                genesis: Default::default(),
+                                header: SpecHeader {
+                                    parent_hash,
+                                    ..Default::default()
+                                },
+                                extrinsics: batch.into_inner(),
+                                timestamp: None,
+                                unsealed: false,
+                                fail_on: fail_on,
+                                fill_block: false,
+                                fill_from: None,
+                                fill_to: None,
+                            },
+                        )?;
+
+                        // The next block is built on top of this one.
+                        parent_hash = Some(block.header.hash().into());
+                        chain.push(import_built_block(client, block, apply_results, fees)?);
+                    }
+
+                    Ok(BlockCmdResult::BuildChain(chain))
                 }
-                CallCmd::ExecuteBlocks { blocks } => {
-                    // Create the block by calling the runtime APIs.
-                    let client = ClientInMem::new()?;
-                    let rt = client.runtime_api();
+                CallCmd::ExecuteBlocks { genesis, blocks } => {
+                    let client = session.client(genesis.load()?)?;
 
                     // Convert into runtime native type.
                     let blocks = blocks
@@ -120,17 +202,589 @@ module!(
                         .map(|raw| Block::try_from(raw))
                         .collect::<Result<Vec<Block>>>()?;
 
+                    // Each block is executed on top of the state of the
+                    // previous one.
+                    let mut executed = vec![];
                     for block in blocks {
-                        let at = BlockId::Hash(block.header.parent_hash.clone().try_into()?);
+                        let hash = block.header.hash();
+                        let number = block.header.number;
+                        let state_root = block.header.state_root;
+
+                        let logs = client.execute_block(block)?;
 
-                        rt.execute_block(&at, block.try_into()?).map_err(|err| {
-                            failure::err_msg(format!("Failed to execute block: {}", err))
-                        })?;
+                        executed.push(ExecutedBlock {
+                            hash: hash.into(),
+                            number: number,
+                            state_root: state_root.into(),
+                            logs: logs,
+                        });
                     }
 
-                    Ok(BlockCmdResult::ExecuteBlocks)
+                    Ok(BlockCmdResult::ExecuteBlocks(executed))
                 }
             }
         }
     }
 );
+
+// Fills in the parent hash and the block number of the header, if those were
+// not specified. By default, blocks are built on top of the best block of the
+// session chain.
+fn complete_header(client: &ClientInMem, header: &mut SpecHeader) -> Result<()> {
+    let (parent_hash, parent_number) = match (&header.parent_hash, &header.number) {
+        (Some(hash), _) => {
+            let hash = H256::try_from(hash.clone())?;
+            (hash, client.block_number(hash)?)
+        }
+        (None, Some(number)) => {
+            let number = BlockNumber::try_from(number.clone())?.saturating_sub(1);
+            (client.block_hash(number)?, number)
+        }
+        (None, None) => client.best_block(),
+    };
+
+    if header.parent_hash.is_none() {
+        header.parent_hash = Some(parent_hash.into());
+    }
+
+    if header.number.is_none() {
+        header.number = Some((parent_number + 1).into());
+    }
+
+    Ok(())
+}
+
+// Imports the built block into the session chain and reports its usage.
+fn import_built_block(
+    client: &ClientInMem,
+    block: Block,
+    apply_results: Vec<SpecApplyResult>,
+    fees: Vec<ExtrinsicFee>,
+) -> Result<BuiltBlock> {
+    client.import_block(block.clone())?;
+
+    // The consumed weight remains in storage until the next block gets
+    // initialized. It is split into the normal and operational class.
+    let key = storage_prefix("System", "BlockWeight");
+    let weight = match client.storage(block.header.hash(), &key)? {
+        Some(raw) => {
+            let (normal, operational) = <(Weight, Weight)>::decode(&mut raw.as_slice())?;
+            normal + operational
+        }
+        None => 0,
+    };
+
+    Ok(BuiltBlock {
+        usage: BlockUsage {
+            weight: weight,
+            max_weight: MaximumBlockWeight::get(),
+            length: block.encode().len() as u32,
+            max_length: MaximumBlockLength::get(),
+        },
+        block: block.into(),
+        apply_results: apply_results,
+        fees: fees,
+    })
+}
+
+/// Builds a block on top of the session chain by calling the runtime APIs. The
+/// block is not imported. Returns the block along with the apply result and
+/// the fee of each extrinsic, excluding inherents.
+pub fn build_block(
+    client: &ClientInMem,
+    mut spec_block: SpecBlock,
+) -> Result<(Block, Vec<SpecApplyResult>, Vec<ExtrinsicFee>)> {
+    complete_header(client, &mut spec_block.header)?;
+
+    let timestamp = spec_block.timestamp;
+    let unsealed = spec_block.unsealed;
+    let fail_on = spec_block.fail_on;
+    let fill_block = spec_block.fill_block;
+    let fill_from = spec_block.fill_from.take();
+    let fill_to = spec_block.fill_to.take();
+
+    // Convert into runtime types.
+    let (at, mut header, extrinsics) = spec_block.prep()?;
+
+    let timestamp = timestamp.unwrap_or(slot_timestamp(header.number));
+
+    // The slot digest must be present before the block is initialized, since
+    // the runtime includes it in the final header. A slot digest specified in
+    // the header takes precedence.
+    let author = if unsealed {
+        None
+    } else {
+        let slot = match header.digest().logs().iter().find_map(|item| match item {
+            DigestItem::PreRuntime(AURA_ENGINE_ID, data) => Some(data),
+            _ => None,
+        }) {
+            Some(data) => u64::decode(&mut data.as_slice())
+                .map_err(|_| failure::err_msg("Invalid slot in Aura pre-runtime digest"))?,
+            None => {
+                let slot = timestamp / SLOT_DURATION;
+                header
+                    .digest_mut()
+                    .push(DigestItem::PreRuntime(AURA_ENGINE_ID, slot.encode()));
+                slot
+            }
+        };
+
+        Some(aura_author(client, &at, slot)?)
+    };
+
+    let rt = client.runtime_api();
+
+    rt.initialize_block(&at, &header)
+        .map_err(|err| failure::err_msg(format!("Failed to initialize block: {}", err)))?;
+
+    // The timestamp inherent is created directly rather than by the runtime,
+    // since the runtime would adjust invalid values. Values which are only
+    // rejected by `check_inherents`, e.g. ones too far in the future, can be
+    // built. The runtime still aborts on timestamps which do not increase by
+    // at least the minimum period, so those cannot be built.
+    let inherents = vec![timestamp_inherent(timestamp)];
+
+    for e in &inherents {
+        let apply_result =
+            SpecApplyResult::from(rt.apply_extrinsic(&at, e.clone()).map_err(|err| {
+                failure::err_msg(format!(
+                    "Timestamp {} rejected by the runtime, it must increase by at least {} ms: {}",
+                    timestamp,
+                    MinimumPeriod::get(),
+                    err
+                ))
+            })?);
+
+        if apply_result != SpecApplyResult::Ok {
+            return Err(failure::err_msg(format!(
+                "Inherent failed: {:?}",
+                apply_result
+            )));
+        }
+    }
+
+    let mut included = vec![];
+    let mut apply_results = vec![];
+    let mut fees = vec![];
+
+    for (index, extr) in extrinsics.into_iter().enumerate() {
+        let apply_result = SpecApplyResult::from(
+            rt.apply_extrinsic(&at, extr.clone())
+                .map_err(|err| failure::err_msg(format!("Failed to apply extrinsic: {}", err)))?,
+        );
+        fees.push(query_fee(client, &at, &extr)?);
+
+        let fail = match fail_on {
+            SpecFailOn::Invalid => apply_result.is_invalid(),
+            SpecFailOn::DispatchError => {
+                apply_result.is_invalid() || apply_result.is_dispatch_error()
+            }
+            SpecFailOn::Never => false,
+        };
+
+        if fail {
+            return Err(failure::err_msg(format!(
+                "Extrinsic {} failed: {:?}",
+                index, apply_result
+            )));
+        }
+
+        // Invalid extrinsics would render the block invalid.
+        if !apply_result.is_invalid() {
+            included.push(extr);
+        }
+
+        apply_results.push(apply_result);
+    }
+
+    if fill_block {
+        let signer = fill_from.unwrap_or(SpecAccountSeed::alice());
+        let account = AccountId::try_from(signer.clone())?;
+        let dest = match fill_to {
+            Some(dest) => AccountId::try_from(dest)?,
+            None => AccountId::try_from(SpecAccountSeed::bob())?,
+        };
+        let mut nonce = client.next_nonce(&account)?;
+
+        loop {
+            let extr = match create_signed_tx(
+                client,
+                signer.clone(),
+                RuntimeCall::Balances(BalancesCall::transfer(dest.clone(), 1)),
+                SpecTxOptions {
+                    nonce: Some(nonce),
+                    ..Default::default()
+                },
+            )? {
+                ExtrinsicOutput::Valid(extr) => UncheckedExtrinsic::try_from(extr)?,
+                ExtrinsicOutput::Mutated { .. } => unreachable!(),
+            };
+
+            match rt
+                .apply_extrinsic(&at, extr.clone())
+                .map_err(|err| failure::err_msg(format!("Failed to apply extrinsic: {}", err)))?
+            {
+                Err(validity) if validity.exhausted_resources() => break,
+                Ok(Ok(())) => {}
+                res => {
+                    return Err(failure::err_msg(format!(
+                        "Failed to fill block: {:?}",
+                        SpecApplyResult::from(res)
+                    )))
+                }
+            }
+
+            fees.push(query_fee(client, &at, &extr)?);
+            included.push(extr);
+            apply_results.push(SpecApplyResult::Ok);
+            nonce += 1;
+        }
+
+        // The transfer which exhausted the block is dropped.
+        client.set_next_nonce(account, nonce);
+    }
+
+    let mut header = rt
+        .finalize_block(&at)
+        .map_err(|_| failure::err_msg("Failed to finalize block"))?;
+
+    // The seal signs the hash of the finalized header.
+    if let Some(author) = author {
+        let signature = author.sign(header.hash().as_ref());
+        header
+            .digest_mut()
+            .push(DigestItem::Seal(AURA_ENGINE_ID, signature.encode()));
+    }
+
+    // Inherents are placed in front of the regular extrinsics, in the same
+    // order they were applied in.
+    Ok((
+        Block {
+            header: header,
+            extrinsics: inherents.into_iter().chain(included).collect(),
+        },
+        apply_results,
+        fees,
+    ))
+}
+
+// Queries the weight and fee of the extrinsic at the parent state of the block.
+fn query_fee(
+    client: &ClientInMem,
+    at: &BlockId,
+    extr: &UncheckedExtrinsic,
+) -> Result<ExtrinsicFee> {
+    let info = client
+        .runtime_api()
+        .query_info(at, extr.clone(), extr.encode().len() as u32)
+        .map_err(|err| failure::err_msg(format!("Failed to query fee: {}", err)))?;
+
+    Ok(ExtrinsicFee {
+        weight: info.weight,
+        class: format!("{:?}", info.class).to_lowercase(),
+        partial_fee: info.partial_fee.to_string(),
+    })
+}
+
+// Returns the key of the Aura authority assigned to the given slot. Only
+// authorities derived from the sample accounts can be used for sealing.
+fn aura_author(client: &ClientInMem, at: &BlockId, slot: u64) -> Result<AuraPair> {
+    let authorities = client
+        .runtime_api()
+        .authorities(at)
+        .map_err(|err| failure::err_msg(format!("Failed to fetch Aura authorities: {}", err)))?;
+
+    if authorities.is_empty() {
+        return Err(failure::err_msg("No Aura authorities available"));
+    }
+
+    let authority = &authorities[(slot % authorities.len() as u64) as usize];
+
+    SAMPLE_ACCOUNTS
+        .iter()
+        .map(|seed| authority_pair_from_seed(seed))
+        .find(|pair| &pair.public() == authority)
+        .ok_or_else(|| {
+            failure::err_msg(format!(
+                "No key available for Aura authority {:?}",
+                authority
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::genesis::{authority_keys_from_seed, GenesisCmd};
+    use crate::builder::test_utils::{balances_extrinsic, build_block_in, run_call};
+    use crate::executor::Session;
+    use crate::primitives::runtime::{AvailableBlockRatio, ExtrinsicBaseWeight};
+    use crate::primitives::SpecChainSpec;
+    use sp_consensus_aura::sr25519::AuthoritySignature;
+
+    fn build(session: &mut Session, yaml: &str) -> Result<BuiltBlock> {
+        run_call::<BlockCmd>(session, yaml).map(|res| match res {
+            BlockCmdResult::BuildBlock(built) => built,
+            _ => panic!("Expected a single block"),
+        })
+    }
+
+    #[test]
+    fn build_block_apply_results() {
+        let mut session = Session::new();
+
+        let mut build_with = |extrinsics: &[&str], fail_on: &str| {
+            let extrinsics = extrinsics
+                .iter()
+                .map(|extr| balances_extrinsic(&mut session, extr))
+                .collect::<Vec<String>>();
+
+            build(
+                &mut session,
+                &format!(
+                    "build: {{ extrinsics: [{}], header: {{ digest: {{ logs: [] }} }}, fail_on: {} }}",
+                    extrinsics.join(", "),
+                    fail_on
+                ),
+            )
+        };
+
+        let transfer = "transfer: { from: alice, to: bob, balance: 100 }";
+        // Exceeds the free balance of dave. The nonce is fixed, since the first
+        // block containing the transfer is never imported.
+        let overdraft =
+            "transfer: { from: dave, to: bob, balance: \"1000000000000000000000000\", nonce: 0 }";
+        let bad_signature =
+            "transfer: { from: alice, to: bob, balance: 100, mutate: bad_signature }";
+
+        let built = build_with(&[transfer, transfer], "dispatch_error").unwrap();
+        assert_eq!(built.apply_results, vec![SpecApplyResult::Ok; 2]);
+
+        assert!(build_with(&[overdraft], "dispatch_error").is_err());
+        let built = build_with(&[overdraft], "invalid").unwrap();
+        assert!(built.apply_results[0].is_dispatch_error());
+
+        assert!(build_with(&[bad_signature], "invalid").is_err());
+        let built = build_with(&[bad_signature, transfer], "never").unwrap();
+        assert!(built.apply_results[0].is_invalid());
+        assert_eq!(built.apply_results[1], SpecApplyResult::Ok);
+
+        // The invalid extrinsic is left out.
+        let block = Block::try_from(built.block).unwrap();
+        assert_eq!(block.extrinsics.len(), 2);
+    }
+
+    #[test]
+    fn fill_block() {
+        let mut session = Session::new();
+
+        let built = build(
+            &mut session,
+            "build: { header: { digest: { logs: [] } }, extrinsics: [], fill_block: true, fill_from: bob, fill_to: dave }",
+        )
+        .unwrap();
+
+        assert!(!built.apply_results.is_empty());
+        assert_eq!(built.apply_results.len(), built.fees.len());
+        assert!(built.usage.length <= built.usage.max_length);
+
+        // The block is full, i.e. one more transfer would exceed the weight
+        // limit of the normal dispatch class.
+        let limit = AvailableBlockRatio::get() * built.usage.max_weight;
+        let transfer_weight = built.fees[0].weight + ExtrinsicBaseWeight::get();
+        assert!(built.usage.weight <= limit);
+        assert!(built.usage.weight + transfer_weight > limit);
+
+        // The transfers are signed by the specified account.
+        let block = Block::try_from(built.block).unwrap();
+        let bob = AccountId::try_from(SpecAccountSeed::bob()).unwrap();
+        for extr in &block.extrinsics[1..] {
+            assert_eq!(extr.signature.as_ref().map(|(addr, _, _)| addr), Some(&bob));
+        }
+
+        // Nonces continue after the last included transfer.
+        let extr = balances_extrinsic(
+            &mut session,
+            "transfer: { from: bob, to: alice, balance: 100 }",
+        );
+        build_block_in(
+            &mut session,
+            &format!(
+                "build: {{ header: {{ digest: {{ logs: [] }} }}, extrinsics: [{}] }}",
+                extr
+            ),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn aura_digests() {
+        let mut session = Session::new();
+
+        let block = build_block_in(
+            &mut session,
+            "build: { header: { digest: { logs: [] } }, extrinsics: [] }",
+        )
+        .unwrap();
+
+        let mut header = block.header;
+        // Block 1 is placed into slot 1.
+        assert_eq!(
+            header.digest().logs().first(),
+            Some(&DigestItem::PreRuntime(AURA_ENGINE_ID, 1u64.encode()))
+        );
+
+        let signature = match header.digest_mut().pop() {
+            Some(DigestItem::Seal(AURA_ENGINE_ID, sig)) => {
+                AuthoritySignature::decode(&mut sig.as_slice()).unwrap()
+            }
+            _ => panic!("Expected an Aura seal"),
+        };
+        assert!(AuraPair::verify(
+            &signature,
+            header.hash(),
+            &authority_keys_from_seed("alice").0
+        ));
+
+        let block = build_block_in(
+            &mut session,
+            "build: { header: { digest: { logs: [] } }, extrinsics: [], unsealed: true }",
+        )
+        .unwrap();
+
+        assert!(block.header.digest().logs().iter().all(|item| match item {
+            DigestItem::PreRuntime(..) | DigestItem::Seal(..) => false,
+            _ => true,
+        }));
+    }
+
+    #[test]
+    fn digest_items() {
+        let mut session = Session::new();
+
+        let block = build_block_in(
+            &mut session,
+            r#"
+            build:
+              header:
+                digest:
+                  logs:
+                    - pre_runtime:
+                        engine: aura
+                        data: "0x0500000000000000"
+                    - other: "0x01"
+                    # Hex encoded `DigestItem::Other`.
+                    - "0x000402"
+              extrinsics: []
+              timestamp: 30000
+        "#,
+        )
+        .unwrap();
+
+        let logs = block.header.digest().logs();
+        assert_eq!(
+            &logs[..3],
+            &[
+                DigestItem::PreRuntime(AURA_ENGINE_ID, 5u64.encode()),
+                DigestItem::Other(vec![1]),
+                DigestItem::Other(vec![2]),
+            ]
+        );
+        // The specified slot digest is not duplicated.
+        assert_eq!(
+            logs.iter()
+                .filter(|item| match item {
+                    DigestItem::PreRuntime(..) => true,
+                    _ => false,
+                })
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn execute_blocks() {
+        let genesis = SpecChainSpec::try_from(
+            run_call::<GenesisCmd>(
+                &mut Session::new(),
+                "custom: { balances: [{ account: bob, balance: 10 DOT }] }",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        let chain = match run_call::<BlockCmd>(
+            &mut Session::with_genesis(genesis.clone()),
+            "build_chain: { count: 2 }",
+        )
+        .unwrap()
+        {
+            BlockCmdResult::BuildChain(chain) => chain
+                .into_iter()
+                .map(|built| built.block)
+                .collect::<Vec<RawBlock>>(),
+            _ => panic!("Expected a chain"),
+        };
+
+        // Blocks are accepted with and without `0x` prefix.
+        let blocks = vec![
+            serde_json::to_string(&chain[0]).unwrap(),
+            serde_json::to_string(&chain[1])
+                .unwrap()
+                .replacen("\"", "\"0x", 1),
+        ];
+
+        // Executed on a new chain with the same genesis.
+        let executed = match run_call::<BlockCmd>(
+            &mut Session::new(),
+            &format!(
+                "execute: {{ genesis: {}, blocks: [{}] }}",
+                serde_json::to_string(&genesis).unwrap(),
+                blocks.join(", ")
+            ),
+        )
+        .unwrap()
+        {
+            BlockCmdResult::ExecuteBlocks(executed) => executed,
+            _ => panic!("Expected executed blocks"),
+        };
+
+        assert_eq!(executed.len(), 2);
+        for (executed, raw) in executed.iter().zip(chain) {
+            let block = Block::try_from(raw).unwrap();
+            assert_eq!(executed.number, block.header.number);
+            assert_eq!(
+                H256::try_from(executed.hash.clone()).unwrap(),
+                block.header.hash()
+            );
+            assert_eq!(
+                H256::try_from(executed.state_root.clone()).unwrap(),
+                block.header.state_root
+            );
+
+            // The test runtime logs each execution.
+            assert!(executed
+                .logs
+                .iter()
+                .any(|log| log.contains("Core_execute_block")));
+        }
+
+        // The state roots do not match on the default genesis.
+        assert!(run_call::<BlockCmd>(
+            &mut Session::new(),
+            &format!("execute: {{ blocks: [{}] }}", blocks[0])
+        )
+        .is_err());
+
+        // The parent of the second block is unknown.
+        assert!(run_call::<BlockCmd>(
+            &mut Session::new(),
+            &format!(
+                "execute: {{ genesis: {}, blocks: [{}] }}",
+                serde_json::to_string(&genesis).unwrap(),
+                blocks[1]
+            )
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,379 @@
+use super::Decoded;
+use crate::primitives::runtime::{
+    AccountId, Balance, Block, Header, Index, RuntimeCall, TimestampCall, UncheckedExtrinsic,
+};
+use crate::primitives::{
+    ExtrinsicOutput, RawBlock, RawExtrinsic, SpecBytes, SpecDigestItem, SpecHash,
+};
+use crate::Result;
+use codec::{Compact, Decode, Encode};
+use frame_support::traits::GetCallMetadata;
+use frame_system::Call as SystemCall;
+use pallet_balances::Call as BalancesCall;
+use pallet_sudo::Call as SudoCall;
+use serde_json::{json, Value};
+use sp_core::crypto::Ss58Codec;
+use sp_core::H256;
+use sp_runtime::generic::{DigestItem, Era};
+use sp_runtime::traits::{BlakeTwo256, Hash, Header as HeaderT};
+use std::convert::{TryFrom, TryInto};
+use structopt::StructOpt;
+
+module!(
+    #[serde(rename = "decode")]
+    struct DecodeCmd;
+
+    enum CallCmd {
+        #[serde(rename = "extrinsic")]
+        Extrinsic {
+            #[structopt(short, long)]
+            data: SpecBytes,
+        },
+        #[serde(rename = "block")]
+        Block {
+            #[structopt(short, long)]
+            data: SpecBytes,
+        },
+        #[serde(rename = "header")]
+        Header {
+            #[structopt(short, long)]
+            data: SpecBytes,
+        },
+        #[serde(rename = "digest_item")]
+        DigestItem {
+            #[structopt(short, long)]
+            data: SpecBytes,
+        },
+    }
+
+    impl DecodeCmd {
+        fn run(self, _session) -> Result<Value> {
+            Ok(match self.call {
+                CallCmd::Extrinsic { data } => decode_extrinsic(&decode_exact(data)?),
+                CallCmd::Block { data } => decode_block(&decode_exact(data)?),
+                CallCmd::Header { data } => decode_header(&decode_exact(data)?),
+                CallCmd::DigestItem { data } => {
+                    json!(SpecDigestItem::from(decode_exact::<DigestItem<H256>>(data)?))
+                }
+            })
+        }
+    }
+);
+
+// Decodes the hex encoded value, rejecting any trailing bytes.
+fn decode_exact<T: Decode>(data: SpecBytes) -> Result<T> {
+    let bytes: Vec<u8> = data.try_into()?;
+    let mut input = bytes.as_slice();
+    let value = T::decode(&mut input)?;
+
+    if !input.is_empty() {
+        return Err(failure::err_msg(format!(
+            "Unexpected {} trailing bytes after decoding",
+            input.len()
+        )));
+    }
+
+    Ok(value)
+}
+
+fn decode_account(account: &AccountId) -> Value {
+    json!({
+        "ss58": account.to_ss58check(),
+        "public_key": SpecBytes::from(account.encode()),
+    })
+}
+
+// Balances can exceed the number range of JSON.
+fn decode_balance(balance: &Balance) -> Value {
+    json!(balance.to_string())
+}
+
+pub fn decode_call(call: &RuntimeCall) -> Value {
+    let metadata = call.get_call_metadata();
+    json!({
+        "module": metadata.pallet_name,
+        "function": metadata.function_name,
+        "arguments": call_arguments(call),
+        "encoded": SpecBytes::from(call.encode()),
+    })
+}
+
+// The arguments of the calls created by the builders, named like in the
+// runtime metadata. The arguments of other calls are only available encoded.
+fn call_arguments(call: &RuntimeCall) -> Value {
+    match call {
+        RuntimeCall::Balances(BalancesCall::transfer(dest, value))
+        | RuntimeCall::Balances(BalancesCall::transfer_keep_alive(dest, value)) => json!({
+            "dest": decode_account(dest),
+            "value": decode_balance(value),
+        }),
+        RuntimeCall::Balances(BalancesCall::set_balance(who, new_free, new_reserved)) => json!({
+            "who": decode_account(who),
+            "new_free": decode_balance(new_free),
+            "new_reserved": decode_balance(new_reserved),
+        }),
+        RuntimeCall::Balances(BalancesCall::force_transfer(source, dest, value)) => json!({
+            "source": decode_account(source),
+            "dest": decode_account(dest),
+            "value": decode_balance(value),
+        }),
+        RuntimeCall::Sudo(SudoCall::sudo(call)) => json!({
+            "call": decode_call(call),
+        }),
+        RuntimeCall::Sudo(SudoCall::sudo_unchecked_weight(call, weight)) => json!({
+            "call": decode_call(call),
+            "weight": weight,
+        }),
+        RuntimeCall::Sudo(SudoCall::set_key(new)) => json!({
+            "new": decode_account(new),
+        }),
+        RuntimeCall::Sudo(SudoCall::sudo_as(who, call)) => json!({
+            "who": decode_account(who),
+            "call": decode_call(call),
+        }),
+        RuntimeCall::System(SystemCall::remark(remark)) => json!({
+            "remark": SpecBytes::from(remark.clone()),
+        }),
+        RuntimeCall::System(SystemCall::set_heap_pages(pages)) => json!({
+            "pages": pages,
+        }),
+        RuntimeCall::System(SystemCall::set_storage(items)) => json!({
+            "items": items
+                .iter()
+                .map(|(key, value)| json!({
+                    "key": SpecBytes::from(key.clone()),
+                    "value": SpecBytes::from(value.clone()),
+                }))
+                .collect::<Vec<Value>>(),
+        }),
+        RuntimeCall::System(SystemCall::kill_storage(keys)) => json!({
+            "keys": keys
+                .iter()
+                .map(|key| SpecBytes::from(key.clone()))
+                .collect::<Vec<SpecBytes>>(),
+        }),
+        RuntimeCall::System(SystemCall::kill_prefix(prefix, subkeys)) => json!({
+            "prefix": SpecBytes::from(prefix.clone()),
+            "subkeys": subkeys,
+        }),
+        // The runtime code is too large to be readable.
+        RuntimeCall::System(SystemCall::set_code(code)) => json!({
+            "code": {
+                "length": code.len(),
+                "hash": SpecHash::from(BlakeTwo256::hash(code)),
+            },
+        }),
+        RuntimeCall::Timestamp(TimestampCall::set(now)) => json!({
+            "now": now,
+        }),
+        _ => Value::Null,
+    }
+}
+
+pub fn decode_extrinsic(extrinsic: &UncheckedExtrinsic) -> Value {
+    let call = decode_call(&extrinsic.function);
+
+    let (signer, signature, extra) = match &extrinsic.signature {
+        Some(signature) => signature,
+        None => {
+            return json!({
+                "signed": false,
+                "call": call,
+            })
+        }
+    };
+
+    // The fields of the signed extensions are private, so the values are
+    // retrieved from their encoding.
+    let era = Era::decode(&mut extra.3.encode().as_slice()).expect("decoded before; qed");
+    let nonce = Compact::<Index>::decode(&mut extra.4.encode().as_slice())
+        .expect("decoded before; qed")
+        .0;
+    let tip = Compact::<Balance>::decode(&mut extra.6.encode().as_slice())
+        .expect("decoded before; qed")
+        .0;
+
+    let era = match era {
+        Era::Immortal => json!("immortal"),
+        Era::Mortal(period, phase) => json!({
+            "mortal": { "period": period, "phase": phase }
+        }),
+    };
+
+    json!({
+        "signed": true,
+        "signer": decode_account(signer),
+        "signature": SpecBytes::from(signature.encode()),
+        "call": call,
+        "signed_extra": {
+            "era": era,
+            "nonce": nonce,
+            "tip": decode_balance(&tip),
+        },
+    })
+}
+
+pub fn decode_header(header: &Header) -> Value {
+    json!({
+        "hash": SpecHash::from(header.hash()),
+        "parent_hash": SpecHash::from(header.parent_hash),
+        "number": header.number,
+        "state_root": SpecHash::from(header.state_root),
+        "extrinsics_root": SpecHash::from(header.extrinsics_root),
+        "digest": header
+            .digest()
+            .logs()
+            .iter()
+            .cloned()
+            .map(SpecDigestItem::from)
+            .collect::<Vec<SpecDigestItem>>(),
+    })
+}
+
+pub fn decode_block(block: &Block) -> Value {
+    json!({
+        "header": decode_header(&block.header),
+        // Allows checking the extrinsics root of the header against the actual
+        // extrinsics.
+        "computed_extrinsics_root": SpecHash::from(BlakeTwo256::ordered_trie_root(
+            block.extrinsics.iter().map(|extr| extr.encode()).collect(),
+        )),
+        "extrinsics": block
+            .extrinsics
+            .iter()
+            .map(decode_extrinsic)
+            .collect::<Vec<Value>>(),
+    })
+}
+
+impl Decoded for RawExtrinsic {
+    fn decoded(&self) -> Option<Value> {
+        Some(match UncheckedExtrinsic::try_from(self.clone()) {
+            Ok(extrinsic) => decode_extrinsic(&extrinsic),
+            Err(err) => json!({ "error": err.to_string() }),
+        })
+    }
+}
+
+impl Decoded for ExtrinsicOutput {
+    fn decoded(&self) -> Option<Value> {
+        // Mutated extrinsics might not be decodable, which is shown as an error.
+        match self {
+            ExtrinsicOutput::Valid(extrinsic) => extrinsic.decoded(),
+            ExtrinsicOutput::Mutated { extrinsic, .. } => extrinsic.decoded(),
+        }
+    }
+}
+
+impl Decoded for RawBlock {
+    fn decoded(&self) -> Option<Value> {
+        Some(match Block::try_from(self.clone()) {
+            Ok(block) => decode_block(&block),
+            Err(err) => json!({ "error": err.to_string() }),
+        })
+    }
+}
+
+impl<T: Decoded> Decoded for Vec<T> {
+    fn decoded(&self) -> Option<Value> {
+        Some(Value::Array(
+            self.iter()
+                .map(|item| item.decoded().unwrap_or(Value::Null))
+                .collect(),
+        ))
+    }
+}
+
+// Already decoded.
+impl Decoded for Value {
+    fn decoded(&self) -> Option<Value> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::test_utils::{balances_extrinsic, build_block_in, run_call};
+    use crate::builder::PalletSudoCmd;
+    use crate::executor::Session;
+    use crate::primitives::SpecAccountSeed;
+
+    #[test]
+    fn decode_extrinsic_and_block() {
+        let mut session = Session::new();
+
+        let extr = balances_extrinsic(
+            &mut Session::new(),
+            "transfer: { from: alice, to: bob, balance: 100, tip: 5, era: { mortal: { period: 64 } } }",
+        );
+
+        let decoded =
+            run_call::<DecodeCmd>(&mut session, &format!("extrinsic: {{ data: {} }}", extr))
+                .unwrap();
+        assert_eq!(decoded["signed"], true);
+        assert_eq!(
+            decoded["signer"]["ss58"],
+            AccountId::try_from(SpecAccountSeed::alice())
+                .unwrap()
+                .to_ss58check()
+        );
+        assert_eq!(decoded["call"]["module"], "Balances");
+        assert_eq!(decoded["call"]["function"], "transfer");
+        assert_eq!(
+            decoded["call"]["arguments"]["dest"]["ss58"],
+            AccountId::try_from(SpecAccountSeed::bob())
+                .unwrap()
+                .to_ss58check()
+        );
+        assert_eq!(decoded["call"]["arguments"]["value"], "100");
+        assert_eq!(decoded["signed_extra"]["era"]["mortal"]["period"], 64);
+        assert_eq!(decoded["signed_extra"]["nonce"], 0);
+        assert_eq!(decoded["signed_extra"]["tip"], "5");
+
+        // Trailing bytes are rejected.
+        let extr = extr.replace("\"", "");
+        assert!(run_call::<DecodeCmd>(
+            &mut session,
+            &format!("extrinsic: {{ data: \"{}00\" }}", extr)
+        )
+        .is_err());
+
+        let block = build_block_in(
+            &mut Session::new(),
+            "build: { header: { digest: { logs: [] } }, extrinsics: [] }",
+        )
+        .unwrap();
+
+        let decoded = run_call::<DecodeCmd>(
+            &mut session,
+            &format!("block: {{ data: \"0x{}\" }}", hex::encode(block.encode())),
+        )
+        .unwrap();
+        assert_eq!(decoded["header"]["number"], 1);
+        assert_eq!(
+            decoded["header"]["digest"][0]["pre_runtime"]["engine"],
+            "aura"
+        );
+        assert_eq!(decoded["extrinsics"][0]["call"]["module"], "Timestamp");
+        assert_eq!(decoded["extrinsics"][0]["call"]["arguments"]["now"], 6000);
+
+        // Nested calls are decoded as well.
+        let extr = match run_call::<PalletSudoCmd>(
+            &mut Session::new(),
+            "sudo: { from: alice, call: { frame_system: { remark: { remark: \"0x0102\" } } } }",
+        )
+        .unwrap()
+        {
+            ExtrinsicOutput::Valid(extr) => extr,
+            _ => panic!("Expected a valid extrinsic"),
+        };
+        let decoded = extr.decoded().unwrap();
+        assert_eq!(decoded["call"]["function"], "sudo");
+        assert_eq!(decoded["call"]["arguments"]["call"]["module"], "System");
+        assert_eq!(
+            decoded["call"]["arguments"]["call"]["arguments"]["remark"],
+            "0x0102"
+        );
+    }
+}
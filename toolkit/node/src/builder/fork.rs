@@ -0,0 +1,315 @@
+use super::Decoded;
+use crate::builder::blocks::build_block;
+use crate::builder::genesis::{export_chain_spec, GenesisCmdResult};
+use crate::primitives::{
+    SpecBlock, SpecBranch, SpecForkBlock, SpecGenesisFormat, SpecGenesisSource, SpecHeader,
+    SpecTestLayout,
+};
+use crate::Result;
+use serde_json::Value;
+use sp_core::H256;
+use sp_runtime::traits::Header as HeaderT;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
+use std::fs;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+const GENESIS_FILE: &str = "genesis.json";
+
+module!(
+    #[serde(rename = "fork")]
+    struct ForkCmd;
+
+    enum CallCmd {
+        #[serde(rename = "build")]
+        Build {
+            #[structopt(flatten)]
+            #[serde(flatten)]
+            genesis: SpecGenesisSource,
+            #[structopt(short, long)]
+            name: String,
+            #[structopt(short, long)]
+            description: String,
+            #[structopt(short, long)]
+            branches: Vec<SpecBranch>,
+            // Directory to write the genesis and the test file to.
+            #[structopt(short, long, parse(from_os_str))]
+            output: Option<PathBuf>,
+            // The format of the written genesis, defaults to the chain spec
+            // the session chain is running on.
+            #[structopt(long)]
+            genesis_format: Option<SpecGenesisFormat>,
+        },
+    }
+
+    impl ForkCmd {
+        fn run(self, session) -> Result<SpecTestLayout<Vec<SpecForkBlock>>> {
+            match self.call {
+                CallCmd::Build {
+                    genesis,
+                    name,
+                    description,
+                    branches,
+                    output,
+                    genesis_format,
+                } => {
+                    let client = session.client(genesis.load()?)?;
+                    let genesis_hash = client.block_hash(0)?;
+
+                    // Keeps track of the block hashes of each branch.
+                    let mut tree: HashMap<String, Vec<H256>> = HashMap::new();
+                    // The parent hash and the extrinsics root of each block.
+                    // Siblings which only differ in their seal would not form
+                    // a meaningful fork.
+                    let mut built = HashSet::new();
+                    let mut data = vec![];
+
+                    for branch in branches {
+                        if tree.contains_key(&branch.name) {
+                            return Err(failure::err_msg(format!(
+                                "Branch \"{}\" is specified multiple times",
+                                branch.name
+                            )));
+                        }
+
+                        let mut parent_hash = if let Some(from) = &branch.from {
+                            let parent_branch = tree.get(from).ok_or(failure::err_msg(format!(
+                                "Branch \"{}\" must be specified before branch \"{}\"",
+                                from, branch.name
+                            )))?;
+
+                            match branch.at {
+                                Some(index) => parent_branch.get(index),
+                                None => parent_branch.last(),
+                            }
+                            .cloned()
+                            .ok_or(failure::err_msg(format!(
+                                "Branch point of branch \"{}\" not found",
+                                branch.name
+                            )))?
+                        } else {
+                            genesis_hash
+                        };
+
+                        let mut hashes = vec![];
+                        for batch in branch.blocks {
+                            let (block, _, _) = build_block(
+                                client,
+                                SpecBlock {
+                                    genesis: Default::default(),
+                                    header: SpecHeader {
+                                        parent_hash: Some(parent_hash.into()),
+                                        ..Default::default()
+                                    },
+                                    extrinsics: batch.into_inner(),
+                                    timestamp: None,
+                                    unsealed: false,
+                                    fail_on: Default::default(),
+                                    fill_block: false,
+                                    fill_from: None,
+                                    fill_to: None,
+                                },
+                            )?;
+
+                            if !built.insert((parent_hash, block.header.extrinsics_root)) {
+                                return Err(failure::err_msg(format!(
+                                    "Block {} of branch \"{}\" is identical to a sibling block",
+                                    block.header.number, branch.name
+                                )));
+                            }
+
+                            client.import_block(block.clone())?;
+
+                            parent_hash = block.header.hash();
+                            hashes.push(parent_hash);
+
+                            data.push(SpecForkBlock::new(
+                                format!(
+                                    "Block {} of branch \"{}\"",
+                                    block.header.number, branch.name
+                                ),
+                                block,
+                            ));
+                        }
+
+                        tree.insert(branch.name, hashes);
+                    }
+
+                    let test = SpecTestLayout {
+                        name: name,
+                        test_ty: "BlockChainFork".to_string(),
+                        description: description,
+                        genesis: GENESIS_FILE.to_string(),
+                        data: data,
+                    };
+
+                    if let Some(output) = output {
+                        let genesis = match genesis_format {
+                            Some(format) => {
+                                export_chain_spec(session.genesis()?.try_into()?, format)?
+                            }
+                            None => GenesisCmdResult::ChainSpec(session.genesis()?),
+                        };
+
+                        fs::create_dir_all(&output)?;
+                        fs::write(
+                            output.join(GENESIS_FILE),
+                            serde_json::to_string_pretty(&genesis)?,
+                        )?;
+                        fs::write(
+                            output.join(format!("{}.json", test_file_name(&test.name))),
+                            serde_json::to_string_pretty(&test)?,
+                        )?;
+                    }
+
+                    Ok(test)
+                }
+            }
+        }
+    }
+);
+
+// Converts the test name into a file name, e.g. "Fork Awareness" into
+// "fork_awareness".
+fn test_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+// The blocks of the test layout already contain readable headers.
+impl<T> Decoded for SpecTestLayout<T> {
+    fn decoded(&self) -> Option<Value> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::test_utils::{balances_extrinsic, run_call, TempDir};
+    use crate::builder::{BlockCmd, GenesisCmd};
+    use crate::executor::Session;
+    use crate::primitives::SpecChainSpec;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn genesis_file_of_fork() {
+        let dir = TempDir::new("fork");
+
+        let genesis = run_call::<GenesisCmd>(
+            &mut Session::new(),
+            "custom: { accounts: [alice, bob], aura_authorities: [bob] }",
+        )
+        .unwrap();
+        let mut session = Session::with_genesis(SpecChainSpec::try_from(genesis).unwrap());
+
+        let test = run_call::<ForkCmd>(
+            &mut session,
+            &format!(
+                r#"
+            build:
+              name: Fork
+              description: Two blocks on the same parent
+              branches:
+                - name: main
+                  blocks: [[]]
+                - name: side
+                  from: main
+                  at: 0
+                  blocks: [[]]
+              output: "{}"
+        "#,
+                dir.path().display()
+            ),
+        )
+        .unwrap();
+
+        // The written genesis can be used to regenerate the fixtures.
+        let mut session = Session::new();
+        run_call::<BlockCmd>(
+            &mut session,
+            &format!(
+                "build_chain: {{ genesis_file: \"{}\", count: 1 }}",
+                dir.path().join(GENESIS_FILE).display()
+            ),
+        )
+        .unwrap();
+
+        assert_eq!(
+            session.client(None).unwrap().block_hash(0).unwrap(),
+            test.data[0].header.parent_hash
+        );
+    }
+
+    #[test]
+    fn sibling_blocks() {
+        let mut session = Session::new();
+
+        let main = balances_extrinsic(
+            &mut session,
+            "transfer: { from: alice, to: bob, balance: 100 }",
+        );
+        let side = balances_extrinsic(
+            &mut session,
+            "transfer: { from: bob, to: dave, balance: 200 }",
+        );
+
+        let test = run_call::<ForkCmd>(
+            &mut session,
+            &format!(
+                r#"
+            build:
+              name: Fork
+              description: Two blocks on the same parent
+              branches:
+                - name: main
+                  blocks: [[], [{}]]
+                - name: side
+                  from: main
+                  at: 0
+                  blocks: [[{}]]
+        "#,
+                main, side
+            ),
+        )
+        .unwrap();
+
+        // The heads of both branches are siblings, but differ.
+        assert_eq!(test.data.len(), 3);
+        assert_eq!(
+            test.data[1].header.parent_hash,
+            test.data[2].header.parent_hash
+        );
+        assert_ne!(
+            test.data[1].header.extrinsics_root,
+            test.data[2].header.extrinsics_root
+        );
+        assert_ne!(test.data[1].post_state, test.data[2].post_state);
+
+        // Siblings without any difference besides the seal are rejected.
+        assert!(run_call::<ForkCmd>(
+            &mut Session::new(),
+            r#"
+            build:
+              name: Fork
+              description: Two blocks on the same parent
+              branches:
+                - name: main
+                  blocks: [[], []]
+                - name: side
+                  from: main
+                  at: 0
+                  blocks: [[]]
+        "#,
+        )
+        .is_err());
+    }
+}
@@ -1,53 +1,300 @@
+use super::Decoded;
+use crate::executor::ClientInMem;
 use crate::primitives::runtime::{
-    AccountId, AuraConfig, BalancesConfig, GenesisConfig, GrandpaConfig, Signature, SudoConfig,
-    SystemConfig, WASM_BINARY,
+    AccountId, AuraConfig, Balance, BalancesConfig, GenesisConfig, GrandpaConfig, Signature,
+    SudoConfig, SystemConfig, WASM_BINARY,
+};
+use crate::primitives::{
+    ChainSpec, ExtrinsicSigner, GenericJson, SpecAccountBalance, SpecAccountSeed, SpecAddress,
+    SpecBytes, SpecChainSpec, SpecChainType, SpecGenesisFormat, SpecGrandpaAuthority, SpecHash,
+    SpecHostGenesis, SpecHostGenesisRaw, SpecHostStorage, SpecStorageEntry,
 };
-use crate::primitives::{ChainSpec, ExtrinsicSigner, SpecAccountSeed, SpecChainSpec};
 use crate::Result;
-use sc_service::ChainType;
-use sp_consensus_aura::sr25519::AuthorityId as AuraId;
+use sc_service::{ChainType, Properties};
+use serde_json::{json, Value};
+use sp_consensus_aura::sr25519::{AuthorityId as AuraId, AuthorityPair as AuraPair};
 use sp_core::{sr25519, Pair, Public};
-use sp_finality_grandpa::AuthorityId as GrandpaId;
-use sp_runtime::traits::{IdentifyAccount, Verify};
+use sp_finality_grandpa::{AuthorityId as GrandpaId, AuthorityWeight};
+use sp_runtime::traits::{Header as HeaderT, IdentifyAccount, Verify};
+use sp_runtime::BuildStorage;
+use std::collections::BTreeMap;
 use std::convert::{TryFrom, TryInto};
+use std::str::FromStr;
 use structopt::StructOpt;
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum GenesisCmdResult {
+    ChainSpec(SpecChainSpec),
+    HostGenesis(SpecHostGenesis),
+}
+
+impl TryFrom<GenesisCmdResult> for SpecChainSpec {
+    type Error = failure::Error;
+
+    fn try_from(value: GenesisCmdResult) -> Result<Self> {
+        match value {
+            GenesisCmdResult::ChainSpec(chain_spec) => Ok(chain_spec),
+            GenesisCmdResult::HostGenesis(_) => Err(failure::err_msg(
+                "The genesis file of Polkadot Host tests is not a chain spec",
+            )),
+        }
+    }
+}
+
 module!(
     #[serde(rename = "genesis")]
     struct GenesisCmd;
 
     enum CallCmd {
         #[serde(rename = "default")]
-        Default {},
+        Default {
+            // Applied to the genesis storage, after it's built.
+            #[structopt(long)]
+            #[serde(default)]
+            storage: Vec<SpecStorageEntry>,
+            #[structopt(short, long, default_value = "spec")]
+            #[serde(default)]
+            format: SpecGenesisFormat,
+        },
         #[serde(rename = "custom")]
         Custom {
+            // Accounts endowed with the default balance of `1 << 60`.
             #[structopt(short, long)]
+            #[serde(default)]
             accounts: Vec<SpecAccountSeed>,
+            #[structopt(short, long)]
+            #[serde(default)]
+            balances: Vec<SpecAccountBalance>,
+            // Defaults to alice.
+            #[structopt(short, long)]
+            sudo: Option<SpecAddress>,
+            // Both authority sets default to alice if left empty.
+            #[structopt(long)]
+            #[serde(default)]
+            aura_authorities: Vec<SpecAddress>,
+            #[structopt(long)]
+            #[serde(default)]
+            grandpa_authorities: Vec<SpecGrandpaAuthority>,
+            #[structopt(long)]
+            name: Option<String>,
+            #[structopt(long)]
+            id: Option<String>,
+            #[structopt(long)]
+            chain_type: Option<SpecChainType>,
+            #[structopt(long)]
+            properties: Option<GenericJson>,
+            // Applied to the genesis storage, after it's built.
+            #[structopt(long)]
+            #[serde(default)]
+            storage: Vec<SpecStorageEntry>,
+            #[structopt(short, long, default_value = "spec")]
+            #[serde(default)]
+            format: SpecGenesisFormat,
         },
     }
 
     impl GenesisCmd {
-        fn run(self) -> Result<SpecChainSpec> {
+        fn run(self, _session) -> Result<GenesisCmdResult> {
             match self.call {
-                CallCmd::Default {} => gen_chain_spec_default()?.try_into(),
-                CallCmd::Custom { accounts } => gen_chain_spec_with_accounts(
-                    accounts
-                        .into_iter()
-                        .map(|seed| ExtrinsicSigner::try_from(seed).map(|pair| pair.public().into()))
-                        .collect::<Result<Vec<AccountId>>>()?,
-                )?
-                .try_into(),
+                CallCmd::Default { storage, format } => export_chain_spec(
+                    apply_storage(gen_chain_spec_default()?, storage)?,
+                    format,
+                ),
+                CallCmd::Custom {
+                    accounts,
+                    balances,
+                    sudo,
+                    aura_authorities,
+                    grandpa_authorities,
+                    name,
+                    id,
+                    chain_type,
+                    properties,
+                    storage,
+                    format,
+                } => {
+                    let mut params = GenesisParams::default();
+
+                    for seed in accounts {
+                        params
+                            .balances
+                            .push((AccountId::try_from(seed)?, DEFAULT_BALANCE));
+                    }
+                    for entry in balances {
+                        params
+                            .balances
+                            .push((entry.account.try_into()?, entry.balance.try_into()?));
+                    }
+                    if let Some(sudo) = sudo {
+                        params.sudo = sudo.try_into()?;
+                    }
+                    if !aura_authorities.is_empty() {
+                        params.aura_authorities = aura_authorities
+                            .into_iter()
+                            .map(AuraId::try_from)
+                            .collect::<Result<Vec<AuraId>>>()?;
+                    }
+                    if !grandpa_authorities.is_empty() {
+                        params.grandpa_authorities = grandpa_authorities
+                            .into_iter()
+                            .map(TryFrom::try_from)
+                            .collect::<Result<Vec<(GrandpaId, AuthorityWeight)>>>()?;
+                    }
+                    if let Some(name) = name {
+                        params.name = name;
+                    }
+                    if let Some(id) = id {
+                        params.id = id;
+                    }
+                    if let Some(chain_type) = chain_type {
+                        params.chain_type = chain_type.into();
+                    }
+                    params.properties = properties.map(Into::into);
+
+                    export_chain_spec(apply_storage(gen_chain_spec(params)?, storage)?, format)
+                }
             }
         }
     }
 );
 
-impl GenesisCmd {
-    pub fn default() -> Self {
-        GenesisCmd {
-            call: CallCmd::Default {},
+// The balance of endowed accounts if not specified otherwise.
+pub const DEFAULT_BALANCE: Balance = 1 << 60;
+
+// The parameters of a custom genesis. The default matches the development
+// chain, without any endowed accounts.
+pub struct GenesisParams {
+    pub name: String,
+    pub id: String,
+    pub chain_type: ChainType,
+    pub balances: Vec<(AccountId, Balance)>,
+    pub sudo: AccountId,
+    pub aura_authorities: Vec<AuraId>,
+    pub grandpa_authorities: Vec<(GrandpaId, AuthorityWeight)>,
+    pub properties: Option<Properties>,
+}
+
+impl Default for GenesisParams {
+    fn default() -> Self {
+        let (aura, grandpa) = authority_keys_from_seed("alice");
+
+        GenesisParams {
+            name: "Development".to_string(),
+            id: "dev".to_string(),
+            chain_type: ChainType::Development,
+            balances: vec![],
+            sudo: get_account_id_from_seed::<sr25519::Public>("alice"),
+            aura_authorities: vec![aura],
+            grandpa_authorities: vec![(grandpa, 1)],
+            properties: None,
+        }
+    }
+}
+
+impl Decoded for GenesisCmdResult {
+    fn decoded(&self) -> Option<Value> {
+        None
+    }
+}
+
+// Inserts or deletes the given entries of the genesis storage. The resulting
+// chain spec contains the genesis as raw storage, since the entries can't be
+// represented in the genesis config of the pallets.
+pub fn apply_storage(chain_spec: ChainSpec, entries: Vec<SpecStorageEntry>) -> Result<ChainSpec> {
+    if entries.is_empty() {
+        return Ok(chain_spec);
+    }
+
+    let mut json: Value = serde_json::from_str(&chain_spec.as_json(true).map_err(|err| {
+        failure::err_msg(format!("Failed to parse chain spec as json: {}", err))
+    })?)?;
+
+    let raw = json
+        .pointer_mut("/genesis/raw")
+        .ok_or(failure::err_msg("Chain spec contains no raw genesis"))?;
+
+    for entry in entries {
+        let key = SpecBytes::from(Vec::<u8>::try_from(entry.key)?);
+
+        let trie = match entry.child {
+            Some(child) => raw["childrenDefault"]
+                .as_object_mut()
+                .ok_or(failure::err_msg("Invalid child tries in raw genesis"))?
+                .entry(SpecBytes::from(Vec::<u8>::try_from(child)?).as_str())
+                .or_insert(json!({})),
+            None => &mut raw["top"],
+        }
+        .as_object_mut()
+        .ok_or(failure::err_msg("Invalid storage in raw genesis"))?;
+
+        match entry.value {
+            Some(value) => {
+                trie.insert(
+                    key.as_str().to_string(),
+                    json!(SpecBytes::from(Vec::<u8>::try_from(value)?)),
+                );
+            }
+            None => {
+                trie.remove(key.as_str());
+            }
         }
     }
+
+    ChainSpec::from_json_bytes(serde_json::to_vec(&json)?).map_err(|err| {
+        failure::err_msg(format!("Failed to convert bytes into chain spec: {}", err))
+    })
+}
+
+pub fn export_chain_spec(
+    chain_spec: ChainSpec,
+    format: SpecGenesisFormat,
+) -> Result<GenesisCmdResult> {
+    Ok(match format {
+        SpecGenesisFormat::Spec => GenesisCmdResult::ChainSpec(chain_spec.try_into()?),
+        SpecGenesisFormat::Raw => {
+            let json = chain_spec.as_json(true).map_err(|err| {
+                failure::err_msg(format!("Failed to parse chain spec as json: {}", err))
+            })?;
+            GenesisCmdResult::ChainSpec(SpecChainSpec::from_str(&json)?)
+        }
+        SpecGenesisFormat::Host => GenesisCmdResult::HostGenesis(host_genesis(&chain_spec)?),
+    })
+}
+
+// Creates the genesis file of Polkadot Host tests from the raw genesis storage,
+// including child tries.
+pub fn host_genesis(chain_spec: &ChainSpec) -> Result<SpecHostGenesis> {
+    let storage = chain_spec
+        .build_storage()
+        .map_err(|err| failure::err_msg(format!("Failed to build genesis storage: {}", err)))?;
+
+    let to_hex = |data: &[u8]| format!("0x{}", hex::encode(data));
+    let to_hex_map = |map: &BTreeMap<Vec<u8>, Vec<u8>>| {
+        map.iter()
+            .map(|(key, value)| (to_hex(key), to_hex(value)))
+            .collect::<BTreeMap<String, String>>()
+    };
+
+    // The genesis block as created by the client.
+    let header = ClientInMem::new_with_genesis(chain_spec.clone())?.header(0)?;
+
+    Ok(SpecHostGenesis {
+        name: chain_spec.name().to_string(),
+        id: chain_spec.id().to_string(),
+        genesis: SpecHostGenesisRaw {
+            raw: SpecHostStorage {
+                top: to_hex_map(&storage.top),
+                children_default: storage
+                    .children_default
+                    .iter()
+                    .map(|(key, child)| (to_hex(key), to_hex_map(&child.data)))
+                    .collect(),
+            },
+        },
+        genesis_state_root: SpecHash::from(header.state_root),
+        genesis_hash: SpecHash::from(header.hash()),
+    })
 }
 
 /// Generate a crypto pair from seed.
@@ -72,34 +319,55 @@ pub fn authority_keys_from_seed(s: &str) -> (AuraId, GrandpaId) {
     (get_from_seed::<AuraId>(s), get_from_seed::<GrandpaId>(s))
 }
 
+/// Generate an Aura authority key pair, used for sealing blocks.
+pub fn authority_pair_from_seed(s: &str) -> AuraPair {
+    AuraPair::from_string(&format!("//{}", s), None).expect("static values are valid; qed")
+}
+
 pub fn gen_chain_spec_default() -> Result<ChainSpec> {
     gen_chain_spec_with_accounts(vec![
-        get_account_id_from_seed::<<ExtrinsicSigner as Pair>::Public>("//alice"),
-        get_account_id_from_seed::<<ExtrinsicSigner as Pair>::Public>("//bob"),
-        get_account_id_from_seed::<<ExtrinsicSigner as Pair>::Public>("//dave"),
+        get_account_id_from_seed::<<ExtrinsicSigner as Pair>::Public>("alice"),
+        get_account_id_from_seed::<<ExtrinsicSigner as Pair>::Public>("bob"),
+        get_account_id_from_seed::<<ExtrinsicSigner as Pair>::Public>("dave"),
     ])
 }
 
 pub fn gen_chain_spec_with_accounts(endowed_accounts: Vec<AccountId>) -> Result<ChainSpec> {
+    gen_chain_spec(GenesisParams {
+        balances: endowed_accounts
+            .into_iter()
+            .map(|account| (account, DEFAULT_BALANCE))
+            .collect(),
+        ..Default::default()
+    })
+}
+
+pub fn gen_chain_spec(params: GenesisParams) -> Result<ChainSpec> {
     let wasm_binary =
         WASM_BINARY.ok_or(failure::err_msg("Development wasm binary not available"))?;
 
+    let GenesisParams {
+        name,
+        id,
+        chain_type,
+        balances,
+        sudo,
+        aura_authorities,
+        grandpa_authorities,
+        properties,
+    } = params;
+
     Ok(ChainSpec::from_genesis(
-        // Name
-        "Development",
-        // ID
-        "dev",
-        ChainType::Development,
+        &name,
+        &id,
+        chain_type,
         move || {
             testnet_genesis(
                 wasm_binary,
-                // Initial PoA authorities
-                vec![authority_keys_from_seed("alice")],
-                // Sudo account
-                get_account_id_from_seed::<sr25519::Public>("alice"),
-                // Pre-funded accounts
-                &endowed_accounts,
-                true,
+                &aura_authorities,
+                &grandpa_authorities,
+                sudo.clone(),
+                &balances,
             )
         },
         // Bootnodes
@@ -108,8 +376,7 @@ pub fn gen_chain_spec_with_accounts(endowed_accounts: Vec<AccountId>) -> Result<
         None,
         // Protocol ID
         None,
-        // Properties
-        None,
+        properties,
         // Extensions
         None,
     ))
@@ -118,10 +385,10 @@ pub fn gen_chain_spec_with_accounts(endowed_accounts: Vec<AccountId>) -> Result<
 /// Configure initial storage state for FRAME modules.
 fn testnet_genesis(
     wasm_binary: &[u8],
-    initial_authorities: Vec<(AuraId, GrandpaId)>,
+    aura_authorities: &[AuraId],
+    grandpa_authorities: &[(GrandpaId, AuthorityWeight)],
     root_key: AccountId,
-    endowed_accounts: &Vec<AccountId>,
-    _enable_println: bool,
+    balances: &[(AccountId, Balance)],
 ) -> GenesisConfig {
     GenesisConfig {
         frame_system: Some(SystemConfig {
@@ -130,21 +397,13 @@ fn testnet_genesis(
             changes_trie_config: Default::default(),
         }),
         pallet_balances: Some(BalancesConfig {
-            // Configure endowed accounts with initial balance of 1 << 60.
-            balances: endowed_accounts
-                .iter()
-                .cloned()
-                .map(|k| (k, 1 << 60))
-                .collect(),
+            balances: balances.to_vec(),
         }),
         pallet_aura: Some(AuraConfig {
-            authorities: initial_authorities.iter().map(|x| (x.0.clone())).collect(),
+            authorities: aura_authorities.to_vec(),
         }),
         pallet_grandpa: Some(GrandpaConfig {
-            authorities: initial_authorities
-                .iter()
-                .map(|x| (x.1.clone(), 1))
-                .collect(),
+            authorities: grandpa_authorities.to_vec(),
         }),
         pallet_sudo: Some(SudoConfig {
             // Assign network admin rights.
@@ -152,3 +411,190 @@ fn testnet_genesis(
         }),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::test_utils::{build_block_in, run_call};
+    use crate::executor::Session;
+    use crate::primitives::SpecStorageKey;
+    use codec::{Decode, Encode};
+    use sp_consensus_aura::sr25519::AuthoritySignature;
+    use sp_consensus_aura::AURA_ENGINE_ID;
+    use sp_core::hashing::{blake2_128, twox_128};
+    use sp_runtime::generic::DigestItem;
+
+    #[test]
+    fn genesis_formats() {
+        let mut session = Session::new();
+        let genesis_hash = session.client(None).unwrap().block_hash(0).unwrap();
+
+        let mut export = |format: &str| {
+            let spec =
+                run_call::<GenesisCmd>(&mut session, &format!("default: {{ format: {} }}", format))
+                    .unwrap();
+            (spec.clone(), serde_json::to_value(&spec).unwrap())
+        };
+
+        let (_, json) = export("spec");
+        assert!(json["genesis"]["runtime"].is_object());
+
+        // The raw chain spec can be used as genesis and results in the same
+        // genesis block.
+        let (raw, json) = export("raw");
+        assert!(json["genesis"]["raw"]["top"].is_object());
+
+        let mut raw_session = Session::new();
+        assert_eq!(
+            raw_session
+                .client(Some(SpecChainSpec::try_from(raw).unwrap()))
+                .unwrap()
+                .block_hash(0)
+                .unwrap(),
+            genesis_hash
+        );
+
+        let (host, json) = export("host");
+        // The runtime code is part of the genesis storage.
+        let code_key = format!("0x{}", hex::encode(b":code"));
+        assert!(json["genesis"]["raw"]["top"][code_key].is_string());
+        assert!(json["genesis"]["raw"]["childrenDefault"].is_object());
+        assert!(json["genesisStateRoot"].is_string());
+        assert_eq!(json["genesisHash"], format!("{:?}", genesis_hash));
+        // Not a chain spec.
+        assert!(SpecChainSpec::try_from(host).is_err());
+        assert!(
+            ChainSpec::try_from(serde_json::from_value::<SpecChainSpec>(json).unwrap()).is_err()
+        );
+    }
+
+    #[test]
+    fn genesis_storage() {
+        let bob = AccountId::try_from(SpecAccountSeed::bob()).unwrap();
+        let spec = run_call::<GenesisCmd>(
+            &mut Session::new(),
+            &format!(
+                r#"
+            default:
+              storage:
+                - key: "0x0102"
+                  value: "0x{value}"
+                - key: {{ pallet: Sudo, item: Key }}
+                  value: "0x{bob}"
+                - child: "0x{child}"
+                  key: "0x03"
+                  value: "0x04"
+                - key: "0x0102"
+              format: host
+        "#,
+                value = "ff".repeat(1024),
+                bob = hex::encode(bob.encode()),
+                child = hex::encode(b"test"),
+            ),
+        )
+        .unwrap();
+
+        let json = serde_json::to_value(&spec).unwrap();
+        let raw = &json["genesis"]["raw"];
+
+        // Deleted after being inserted.
+        assert!(raw["top"]["0x0102"].is_null());
+        assert_eq!(
+            raw["childrenDefault"][format!("0x{}", hex::encode(b"test"))]["0x03"],
+            "0x04"
+        );
+
+        let sudo_key = format!(
+            "0x{}",
+            hex::encode(&[twox_128(b"Sudo"), twox_128(b"Key")].concat())
+        );
+        assert_eq!(
+            raw["top"][sudo_key],
+            format!("0x{}", hex::encode(bob.encode()))
+        );
+
+        // Map keys are hashed with `blake2_128_concat` by default.
+        let alice = AccountId::try_from(SpecAccountSeed::alice()).unwrap();
+        let key = Vec::<u8>::try_from(
+            serde_yaml::from_str::<SpecStorageKey>(&format!(
+                "{{ pallet: System, item: Account, keys: [\"0x{}\"] }}",
+                hex::encode(alice.encode())
+            ))
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            key,
+            [
+                &twox_128(b"System")[..],
+                &twox_128(b"Account")[..],
+                &blake2_128(&alice.encode())[..],
+                &alice.encode()[..],
+            ]
+            .concat()
+        );
+        assert!(raw["top"][format!("0x{}", hex::encode(&key))].is_string());
+    }
+
+    #[test]
+    fn genesis_custom() {
+        let mut session = Session::new();
+
+        let spec = run_call::<GenesisCmd>(
+            &mut session,
+            r#"
+            custom:
+              balances:
+                - account: bob
+                  balance: 10 DOT
+                - account: eve
+                  balance: 1000000000000
+              sudo: bob
+              aura_authorities:
+                - bob
+                - eve
+              grandpa_authorities:
+                - account: bob
+                  weight: 2
+                - account: eve
+              name: Test chain
+              id: test
+              chain_type: local
+              properties:
+                tokenSymbol: DOT
+                tokenDecimals: 10
+        "#,
+        )
+        .unwrap();
+
+        let json = serde_json::to_value(&spec).unwrap();
+        assert_eq!(json["name"], "Test chain");
+        assert_eq!(json["id"], "test");
+        assert_eq!(json["chainType"], "Local");
+        assert_eq!(json["properties"]["tokenSymbol"], "DOT");
+
+        session
+            .client(Some(SpecChainSpec::try_from(spec).unwrap()))
+            .unwrap();
+
+        // Block 1 is placed into slot 1, which belongs to the second authority.
+        let mut header = build_block_in(
+            &mut session,
+            "build: { header: { digest: { logs: [] } }, extrinsics: [] }",
+        )
+        .unwrap()
+        .header;
+
+        let signature = match header.digest_mut().pop() {
+            Some(DigestItem::Seal(AURA_ENGINE_ID, sig)) => {
+                AuthoritySignature::decode(&mut sig.as_slice()).unwrap()
+            }
+            _ => panic!("Expected an Aura seal"),
+        };
+        assert!(AuraPair::verify(
+            &signature,
+            header.hash(),
+            &authority_keys_from_seed("eve").0
+        ));
+    }
+}
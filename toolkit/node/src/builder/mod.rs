@@ -1,21 +1,42 @@
 use super::Result;
-use crate::primitives::runtime::{AccountId, RuntimeCall, SignedExtra, UncheckedExtrinsic};
+use crate::executor::{ClientInMem, Session};
+use crate::primitives::runtime::{
+    AccountId, Balance, BlockNumber, Index, MaximumBlockLength, RuntimeCall, SignedExtra,
+    UncheckedExtrinsic,
+};
+use crate::primitives::{
+    ExtrinsicOutput, ExtrinsicSigner, SpecAccountSeed, SpecEra, SpecMutation, SpecTxOptions,
+};
 use crate::tool_spec::TaskOutcome;
-use codec::Encode;
+use codec::{Compact, Decode, Encode};
 use serde::de::DeserializeOwned;
 use serde::ser::Serialize;
+use serde_json::Value;
+use sp_api::Core;
 use sp_core::crypto::Pair;
+use sp_core::H256;
 use sp_runtime::generic::{Era, SignedPayload};
-use sp_runtime::traits::SignedExtension;
 use sp_runtime::MultiSignature;
+use std::convert::TryFrom;
+use std::str::FromStr;
 
 pub mod balances;
 pub mod blocks;
+pub mod decode;
+pub mod fork;
 pub mod genesis;
+pub mod sudo;
+pub mod system;
+pub mod timestamp;
 
 pub use balances::PalletBalancesCmd;
 pub use blocks::BlockCmd;
+pub use decode::DecodeCmd;
+pub use fork::ForkCmd;
 pub use genesis::GenesisCmd;
+pub use sudo::PalletSudoCmd;
+pub use system::FrameSystemCmd;
+pub use timestamp::PalletTimestampCmd;
 
 pub trait ModuleInfo {
     fn module_name(&self) -> ModuleName;
@@ -42,17 +63,21 @@ impl FunctionName {
 
 pub trait Builder: Sized + ModuleInfo {
     type Input: DeserializeOwned;
-    type Output: Serialize;
+    type Output: Serialize + Decoded;
+
+    fn run(self, session: &mut Session) -> Result<Self::Output>;
+    fn run_and_print(self, session: &mut Session, mode: OutputMode) -> Result<()> {
+        let module = self.module_name();
+        let function = self.function_name();
+        let data = format_output(self.run(session)?, mode)?;
 
-    fn run(self) -> Result<Self::Output>;
-    fn run_and_print(self) -> Result<()> {
         println!(
             "{}",
             serde_json::to_string_pretty(&TaskOutcome {
                 task_name: Option::<String>::None,
-                module: self.module_name(),
-                function: self.function_name(),
-                data: self.run()?,
+                module: module,
+                function: function,
+                data: data,
             })?
         );
 
@@ -60,42 +85,256 @@ pub trait Builder: Sized + ModuleInfo {
     }
 }
 
-fn create_tx<P: Pair>(pair: P, function: RuntimeCall, nonce: u32) -> Result<UncheckedExtrinsic>
+// Provides the decoded form of a builder output, which is shown along the
+// output in the readable output mode.
+pub trait Decoded {
+    fn decoded(&self) -> Option<Value>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+    // Only the (encoded) output.
+    Encoded,
+    // The output along with its decoded form.
+    Readable,
+}
+
+impl Default for OutputMode {
+    fn default() -> Self {
+        OutputMode::Encoded
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ReadableOutput<T> {
+    encoded: T,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    decoded: Option<Value>,
+}
+
+pub fn format_output<T: Serialize + Decoded>(output: T, mode: OutputMode) -> Result<Value> {
+    Ok(match mode {
+        OutputMode::Encoded => serde_json::to_value(output)?,
+        OutputMode::Readable => serde_json::to_value(ReadableOutput {
+            decoded: output.decoded(),
+            encoded: output,
+        })?,
+    })
+}
+
+// A call which is wrapped by another call, such as `pallet_sudo::sudo`. The
+// call is either specified like a task of the corresponding module, without
+// the fields related to signing, or as a hex encoded call.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SpecCall {
+    Encoded(String),
+    Module(SpecModuleCall),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpecModuleCall {
+    PalletBalances(balances::NestedCall),
+    PalletSudo(Box<sudo::NestedCall>),
+    FrameSystem(system::NestedCall),
+}
+
+impl SpecCall {
+    pub fn into_call(self) -> Result<RuntimeCall> {
+        match self {
+            SpecCall::Encoded(encoded) => {
+                RuntimeCall::decode(&mut hex::decode(encoded.replace("0x", ""))?.as_slice())
+                    .map_err(|err| err.into())
+            }
+            SpecCall::Module(SpecModuleCall::PalletBalances(call)) => call.into_call(),
+            SpecCall::Module(SpecModuleCall::PalletSudo(call)) => call.into_call(),
+            SpecCall::Module(SpecModuleCall::FrameSystem(call)) => call.into_call(),
+        }
+    }
+}
+
+impl FromStr for SpecCall {
+    type Err = failure::Error;
+
+    // On the command line, the call is either hex encoded or specified as
+    // YAML/JSON.
+    fn from_str(val: &str) -> Result<Self> {
+        if val.starts_with("0x") {
+            Ok(SpecCall::Encoded(val.to_string()))
+        } else {
+            Ok(serde_yaml::from_str(val)?)
+        }
+    }
+}
+
+// The values of the signed extensions of an extrinsic, including the values
+// which are only part of the signed payload.
+struct SignedExtraParams {
+    spec_version: u32,
+    tx_version: u32,
+    genesis_hash: H256,
+    era: Era,
+    era_birth_hash: H256,
+    nonce: Index,
+    tip: Balance,
+}
+
+// Creates a signed extrinsic on top of the best block of the session chain.
+// Values which are not specified in the options are taken from that chain.
+fn create_signed_tx(
+    client: &ClientInMem,
+    signer: SpecAccountSeed,
+    function: RuntimeCall,
+    options: SpecTxOptions,
+) -> Result<ExtrinsicOutput> {
+    let pair = ExtrinsicSigner::try_from(signer)?;
+    let account: AccountId = pair.public().into();
+
+    let (_, best_number) = client.best_block();
+    let version = client
+        .runtime_api()
+        .version(&client.best_block_id())
+        .map_err(|err| failure::err_msg(format!("Failed to fetch runtime version: {}", err)))?;
+
+    // The genesis hash is fetched from the chain rather than from the
+    // runtime storage, since the runtime only stores it once block #1 is
+    // executed.
+    let genesis_hash = client.block_hash(0)?;
+
+    let (era, era_birth_hash) = match options.era.unwrap_or(SpecEra::Immortal) {
+        SpecEra::Immortal => (Era::Immortal, genesis_hash),
+        SpecEra::Mortal { period, birth } => {
+            let birth = birth.unwrap_or(best_number) as u64;
+            let era = Era::mortal(period, birth);
+            (era, client.block_hash(era.birth(birth) as BlockNumber)?)
+        }
+    };
+
+    let mut params = SignedExtraParams {
+        spec_version: options.spec_version.unwrap_or(version.spec_version),
+        tx_version: options.tx_version.unwrap_or(version.transaction_version),
+        genesis_hash: options
+            .genesis_hash
+            .map(H256::try_from)
+            .transpose()?
+            .unwrap_or(genesis_hash),
+        era: era,
+        era_birth_hash: era_birth_hash,
+        nonce: match options.nonce {
+            Some(nonce) => nonce,
+            None => client.next_nonce(&account)?,
+        },
+        tip: options.tip.unwrap_or(0),
+    };
+
+    // Inject invalidities which affect the signed payload.
+    match options.mutate {
+        Some(SpecMutation::WrongGenesisHash) => params.genesis_hash.as_bytes_mut()[0] ^= 0xff,
+        Some(SpecMutation::StaleNonce) => {
+            params.nonce =
+                client
+                    .account_nonce(&account)?
+                    .checked_sub(1)
+                    .ok_or(failure::err_msg(
+                        "No stale nonce available for an account with an on-chain nonce of 0",
+                    ))?;
+        }
+        Some(SpecMutation::ExpiredEra) => {
+            // A mortal era with the shortest possible period, which was born
+            // one full period before the next block.
+            let period = 4;
+            let birth = (best_number + 1)
+                .checked_sub(period)
+                .ok_or(failure::err_msg(
+                    "An expired era requires at least three blocks on top of the genesis",
+                ))?;
+
+            params.era = Era::mortal(period as u64, birth as u64);
+            params.era_birth_hash = client.block_hash(birth)?;
+        }
+        Some(SpecMutation::WrongSpecVersion) => {
+            params.spec_version = params.spec_version.wrapping_add(1)
+        }
+        _ => {}
+    }
+
+    // Invalid extrinsics are not expected to be included, so the nonce is
+    // only tracked for valid ones.
+    if options.mutate.is_none() {
+        client.track_nonce(account, params.nonce);
+    }
+
+    let mut extr = create_tx::<ExtrinsicSigner>(pair, function, params)?;
+
+    // Inject invalidities which affect the extrinsic itself.
+    let mutation = if let Some(mutation) = options.mutate {
+        mutation
+    } else {
+        return Ok(ExtrinsicOutput::Valid(extr.into()));
+    };
+
+    let encoded = match mutation {
+        SpecMutation::BadSignature => {
+            if let Some((_, MultiSignature::Sr25519(sig), _)) = extr.signature.as_mut() {
+                sig.0[0] ^= 0xff;
+            }
+
+            extr.encode()
+        }
+        SpecMutation::TruncatedScale => {
+            let mut encoded = extr.encode();
+            encoded.pop();
+            encoded
+        }
+        SpecMutation::OversizedLength => {
+            // Replace the length prefix.
+            let encoded = extr.encode();
+            let mut slice = encoded.as_slice();
+            let _ = Compact::<u32>::decode(&mut slice)?;
+
+            let mut oversized = Compact(MaximumBlockLength::get() + 1).encode();
+            oversized.extend_from_slice(slice);
+            oversized
+        }
+        _ => extr.encode(),
+    };
+
+    Ok(ExtrinsicOutput::Mutated {
+        extrinsic: encoded.into(),
+        mutation: mutation,
+    })
+}
+
+fn create_tx<P: Pair>(
+    pair: P,
+    function: RuntimeCall,
+    params: SignedExtraParams,
+) -> Result<UncheckedExtrinsic>
 where
     AccountId: From<<P as Pair>::Public>,
     MultiSignature: From<<P as Pair>::Signature>,
 {
-    fn extra_err() -> failure::Error {
-        failure::err_msg("Failed to retrieve additionally signed extra")
-    }
-
-    let check_spec_version = frame_system::CheckSpecVersion::new();
-    let check_tx_version = frame_system::CheckTxVersion::new();
-    let check_genesis = frame_system::CheckGenesis::new();
-    let check_era = frame_system::CheckEra::from(Era::Immortal);
-    let check_nonce = frame_system::CheckNonce::from(nonce);
-    let check_weight = frame_system::CheckWeight::new();
-    let payment = pallet_transaction_payment::ChargeTransactionPayment::from(0);
-
-    #[rustfmt::skip]
-    let additional_extra = (
-        check_spec_version.additional_signed().map_err(|_| extra_err())?,
-        check_tx_version.additional_signed().map_err(|_| extra_err())?,
-        check_genesis.additional_signed().map_err(|_| extra_err())?,
-        check_era.additional_signed().map_err(|_| extra_err())?,
-        check_nonce.additional_signed().map_err(|_| extra_err())?,
-        check_weight.additional_signed().map_err(|_| extra_err())?,
-        payment.additional_signed().map_err(|_| extra_err())?,
+    let extra: SignedExtra = (
+        frame_system::CheckSpecVersion::new(),
+        frame_system::CheckTxVersion::new(),
+        frame_system::CheckGenesis::new(),
+        frame_system::CheckEra::from(params.era),
+        frame_system::CheckNonce::from(params.nonce),
+        frame_system::CheckWeight::new(),
+        pallet_transaction_payment::ChargeTransactionPayment::from(params.tip),
     );
 
-    let extra: SignedExtra = (
-        check_spec_version,
-        check_tx_version,
-        check_genesis,
-        check_era,
-        check_nonce,
-        check_weight,
-        payment,
+    // The additionally signed values are not fetched from the signed
+    // extensions themselves, since those read the runtime storage directly.
+    let additional_extra = (
+        params.spec_version,
+        params.tx_version,
+        params.genesis_hash,
+        params.era_birth_hash,
+        (),
+        (),
+        (),
     );
 
     let payload = SignedPayload::from_raw(function, extra, additional_extra);
@@ -111,3 +350,110 @@ where
         extra,
     ))
 }
+
+// Helpers for the tests of the builders and the tool spec.
+#[cfg(test)]
+pub mod test_utils {
+    use super::blocks::BlockCmdResult;
+    use super::{BlockCmd, Builder, PalletBalancesCmd};
+    use crate::executor::Session;
+    use crate::primitives::runtime::Block;
+    use crate::primitives::ExtrinsicOutput;
+    use crate::Result;
+    use std::convert::TryFrom;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::{env, fs, process};
+
+    // Runs a single call of the builder, specified like in a tool spec.
+    pub fn run_call<C>(session: &mut Session, yaml: &str) -> Result<C::Output>
+    where
+        C: Builder + From<<C as Builder>::Input>,
+    {
+        C::from(serde_yaml::from_str::<C::Input>(yaml)?).run(session)
+    }
+
+    // Builds a single block from the given `block` call on the session chain.
+    pub fn build_block_in(session: &mut Session, yaml: &str) -> Result<Block> {
+        run_call::<BlockCmd>(session, yaml).map(|res| match res {
+            BlockCmdResult::BuildBlock(built) => Block::try_from(built.block).unwrap(),
+            _ => panic!("Expected a single block"),
+        })
+    }
+
+    // Creates the extrinsic of the given `pallet_balances` call, JSON encoded
+    // to be inserted into a `block` call.
+    pub fn balances_extrinsic(session: &mut Session, yaml: &str) -> String {
+        let extr = match run_call::<PalletBalancesCmd>(session, yaml).unwrap() {
+            ExtrinsicOutput::Valid(extr) => extr,
+            ExtrinsicOutput::Mutated { extrinsic, .. } => extrinsic,
+        };
+
+        serde_json::to_string(&extr).unwrap()
+    }
+
+    // A temporary directory which is unique to the test and removed on drop.
+    pub struct TempDir(PathBuf);
+
+    impl TempDir {
+        pub fn new(name: &str) -> Self {
+            static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+            let dir = env::temp_dir().join(format!(
+                "toolkit_{}_{}_{}",
+                name,
+                process::id(),
+                COUNTER.fetch_add(1, Ordering::SeqCst)
+            ));
+            fs::create_dir_all(&dir).unwrap();
+
+            TempDir(dir)
+        }
+        pub fn path(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_utils::run_call;
+    use super::*;
+    use crate::executor::Session;
+
+    #[test]
+    fn readable_output() {
+        let mut session = Session::new();
+
+        let result = run_call::<BlockCmd>(
+            &mut session,
+            "build: { header: { digest: { logs: [] } }, extrinsics: [] }",
+        )
+        .unwrap();
+
+        let output = format_output(result, OutputMode::Readable).unwrap();
+        assert!(output["encoded"]["block"].is_string());
+        assert_eq!(output["decoded"]["header"]["number"], 1);
+        assert_eq!(
+            output["decoded"]["header"]["extrinsics_root"],
+            output["decoded"]["computed_extrinsics_root"]
+        );
+
+        let result = run_call::<PalletBalancesCmd>(
+            &mut session,
+            "transfer: { from: alice, to: bob, balance: 100, mutate: truncated_scale }",
+        )
+        .unwrap();
+
+        // Invalid extrinsics are shown with the decoding error.
+        let output = format_output(result, OutputMode::Readable).unwrap();
+        assert_eq!(output["encoded"]["mutation"], "truncated_scale");
+        assert!(output["decoded"]["error"].is_string());
+    }
+}
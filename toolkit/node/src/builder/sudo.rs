@@ -0,0 +1,215 @@
+use super::{create_signed_tx, SpecCall};
+use crate::primitives::runtime::{AccountId, RuntimeCall};
+use crate::primitives::{
+    ExtrinsicOutput, SpecAccountSeed, SpecAddress, SpecGenesisSource, SpecTxOptions,
+};
+use crate::Result;
+use pallet_sudo::Call as SudoCall;
+use std::convert::TryFrom;
+use structopt::StructOpt;
+
+module!(
+    #[serde(rename = "pallet_sudo")]
+    struct PalletSudoCmd;
+
+    enum CallCmd {
+        #[serde(rename = "sudo")]
+        Sudo {
+            #[structopt(flatten)]
+            #[serde(flatten)]
+            genesis: SpecGenesisSource,
+            #[structopt(short, long)]
+            from: SpecAccountSeed,
+            #[structopt(short, long)]
+            call: SpecCall,
+            #[structopt(flatten)]
+            #[serde(flatten)]
+            options: SpecTxOptions,
+        },
+        #[serde(rename = "sudo_unchecked_weight")]
+        SudoUncheckedWeight {
+            #[structopt(flatten)]
+            #[serde(flatten)]
+            genesis: SpecGenesisSource,
+            #[structopt(short, long)]
+            from: SpecAccountSeed,
+            #[structopt(short, long)]
+            call: SpecCall,
+            #[structopt(short, long)]
+            weight: u64,
+            #[structopt(flatten)]
+            #[serde(flatten)]
+            options: SpecTxOptions,
+        },
+        #[serde(rename = "set_key")]
+        SetKey {
+            #[structopt(flatten)]
+            #[serde(flatten)]
+            genesis: SpecGenesisSource,
+            #[structopt(short, long)]
+            from: SpecAccountSeed,
+            #[structopt(short, long)]
+            new: SpecAddress,
+            #[structopt(flatten)]
+            #[serde(flatten)]
+            options: SpecTxOptions,
+        },
+        #[serde(rename = "sudo_as")]
+        SudoAs {
+            #[structopt(flatten)]
+            #[serde(flatten)]
+            genesis: SpecGenesisSource,
+            #[structopt(short, long)]
+            from: SpecAccountSeed,
+            #[structopt(short, long)]
+            who: SpecAddress,
+            #[structopt(short, long)]
+            call: SpecCall,
+            #[structopt(flatten)]
+            #[serde(flatten)]
+            options: SpecTxOptions,
+        },
+    }
+
+    impl PalletSudoCmd {
+        fn run(self, session) -> Result<ExtrinsicOutput> {
+            let (genesis, from, options) = match &self.call {
+                CallCmd::Sudo {
+                    genesis,
+                    from,
+                    options,
+                    ..
+                }
+                | CallCmd::SudoUncheckedWeight {
+                    genesis,
+                    from,
+                    options,
+                    ..
+                }
+                | CallCmd::SetKey {
+                    genesis,
+                    from,
+                    options,
+                    ..
+                }
+                | CallCmd::SudoAs {
+                    genesis,
+                    from,
+                    options,
+                    ..
+                } => (genesis.clone(), from.clone(), options.clone()),
+            };
+
+            create_signed_tx(
+                session.client(genesis.load()?)?,
+                from,
+                NestedCall::from(self.call).into_call()?,
+                options,
+            )
+        }
+    }
+);
+
+// The call as nested into another call, such as `pallet_sudo::sudo_as`,
+// without any fields related to signing.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum NestedCall {
+    #[serde(rename = "sudo")]
+    Sudo { call: SpecCall },
+    #[serde(rename = "sudo_unchecked_weight")]
+    SudoUncheckedWeight { call: SpecCall, weight: u64 },
+    #[serde(rename = "set_key")]
+    SetKey { new: SpecAddress },
+    #[serde(rename = "sudo_as")]
+    SudoAs { who: SpecAddress, call: SpecCall },
+}
+
+impl From<CallCmd> for NestedCall {
+    fn from(value: CallCmd) -> Self {
+        match value {
+            CallCmd::Sudo { call, .. } => NestedCall::Sudo { call: call },
+            CallCmd::SudoUncheckedWeight { call, weight, .. } => NestedCall::SudoUncheckedWeight {
+                call: call,
+                weight: weight,
+            },
+            CallCmd::SetKey { new, .. } => NestedCall::SetKey { new: new },
+            CallCmd::SudoAs { who, call, .. } => NestedCall::SudoAs {
+                who: who,
+                call: call,
+            },
+        }
+    }
+}
+
+impl NestedCall {
+    pub fn into_call(self) -> Result<RuntimeCall> {
+        Ok(RuntimeCall::Sudo(match self {
+            NestedCall::Sudo { call } => SudoCall::sudo(Box::new(call.into_call()?)),
+            NestedCall::SudoUncheckedWeight { call, weight } => {
+                SudoCall::sudo_unchecked_weight(Box::new(call.into_call()?), weight)
+            }
+            NestedCall::SetKey { new } => SudoCall::set_key(AccountId::try_from(new)?),
+            NestedCall::SudoAs { who, call } => {
+                SudoCall::sudo_as(AccountId::try_from(who)?, Box::new(call.into_call()?))
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::test_utils::run_call;
+    use crate::executor::Session;
+    use crate::primitives::runtime::UncheckedExtrinsic;
+    use sp_core::crypto::{Pair, Ss58Codec};
+    use sp_core::sr25519;
+
+    #[test]
+    fn signer_of_calls() {
+        let sudo = |yaml: &str| run_call::<PalletSudoCmd>(&mut Session::new(), yaml);
+
+        assert!(sudo(
+            "sudo: { from: alice, call: { pallet_balances: { transfer: { to: bob, balance: 100 } } } }"
+        )
+        .is_ok());
+
+        // The signer of the extrinsic is required.
+        assert!(sudo("set_key: { new: bob }").is_err());
+        assert!(sudo(
+            "sudo: { call: { pallet_balances: { transfer: { to: bob, balance: 100 } } } }"
+        )
+        .is_err());
+
+        // Nested calls are not signed.
+        assert!(sudo(
+            "sudo: { from: alice, call: { pallet_balances: { transfer: { from: bob, to: bob, balance: 100 } } } }"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn key_addresses() {
+        let mut session = Session::new();
+        let mut set_key = |new: &str| {
+            let yaml = format!("set_key: {{ from: alice, new: \"{}\" }}", new);
+            let extr = match run_call::<PalletSudoCmd>(&mut session, &yaml).unwrap() {
+                ExtrinsicOutput::Valid(extr) => extr,
+                _ => panic!("Expected a valid extrinsic"),
+            };
+            match UncheckedExtrinsic::try_from(extr).unwrap().function {
+                RuntimeCall::Sudo(SudoCall::set_key(new)) => new,
+                _ => panic!("Expected a set_key call"),
+            }
+        };
+
+        let bob = sr25519::Pair::from_string("//Bob", None).unwrap().public();
+        let expected = AccountId::from(bob.clone());
+
+        // Sample accounts, hex encoded public keys and SS58 addresses.
+        assert_eq!(set_key("bob"), expected);
+        assert_eq!(set_key(&format!("0x{}", hex::encode(bob.0))), expected);
+        assert_eq!(set_key(&bob.to_ss58check()), expected);
+    }
+}
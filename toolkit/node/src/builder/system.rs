@@ -0,0 +1,355 @@
+use super::create_signed_tx;
+use crate::primitives::runtime::{RuntimeCall, WASM_BINARY};
+use crate::primitives::{
+    ExtrinsicOutput, SpecAccountSeed, SpecBytes, SpecGenesisSource, SpecKeyValue, SpecTxOptions,
+};
+use crate::Result;
+use frame_system::Call as SystemCall;
+use std::convert::TryInto;
+use std::fs;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+module!(
+    #[serde(rename = "frame_system")]
+    struct FrameSystemCmd;
+
+    enum CallCmd {
+        #[serde(rename = "remark")]
+        Remark {
+            #[structopt(flatten)]
+            #[serde(flatten)]
+            genesis: SpecGenesisSource,
+            #[structopt(short, long)]
+            from: SpecAccountSeed,
+            #[structopt(short, long)]
+            remark: SpecBytes,
+            #[structopt(flatten)]
+            #[serde(flatten)]
+            options: SpecTxOptions,
+        },
+        #[serde(rename = "set_heap_pages")]
+        SetHeapPages {
+            #[structopt(flatten)]
+            #[serde(flatten)]
+            genesis: SpecGenesisSource,
+            #[structopt(short, long)]
+            from: SpecAccountSeed,
+            #[structopt(short, long)]
+            pages: u64,
+            #[structopt(flatten)]
+            #[serde(flatten)]
+            options: SpecTxOptions,
+        },
+        #[serde(rename = "set_storage")]
+        SetStorage {
+            #[structopt(flatten)]
+            #[serde(flatten)]
+            genesis: SpecGenesisSource,
+            #[structopt(short, long)]
+            from: SpecAccountSeed,
+            #[structopt(short, long)]
+            items: Vec<SpecKeyValue>,
+            #[structopt(flatten)]
+            #[serde(flatten)]
+            options: SpecTxOptions,
+        },
+        #[serde(rename = "kill_storage")]
+        KillStorage {
+            #[structopt(flatten)]
+            #[serde(flatten)]
+            genesis: SpecGenesisSource,
+            #[structopt(short, long)]
+            from: SpecAccountSeed,
+            #[structopt(short, long)]
+            keys: Vec<SpecBytes>,
+            #[structopt(flatten)]
+            #[serde(flatten)]
+            options: SpecTxOptions,
+        },
+        #[serde(rename = "kill_prefix")]
+        KillPrefix {
+            #[structopt(flatten)]
+            #[serde(flatten)]
+            genesis: SpecGenesisSource,
+            #[structopt(short, long)]
+            from: SpecAccountSeed,
+            #[structopt(short, long)]
+            prefix: SpecBytes,
+            #[structopt(short, long)]
+            subkeys: u32,
+            #[structopt(flatten)]
+            #[serde(flatten)]
+            options: SpecTxOptions,
+        },
+        // The runtime code is either hex encoded or read from a file. If
+        // neither is specified, the runtime of the toolkit is used.
+        #[serde(rename = "set_code")]
+        SetCode {
+            #[structopt(flatten)]
+            #[serde(flatten)]
+            genesis: SpecGenesisSource,
+            #[structopt(short, long)]
+            from: SpecAccountSeed,
+            #[structopt(short, long)]
+            code: Option<SpecBytes>,
+            #[structopt(long, parse(from_os_str))]
+            code_file: Option<PathBuf>,
+            #[structopt(flatten)]
+            #[serde(flatten)]
+            options: SpecTxOptions,
+        },
+    }
+
+    impl FrameSystemCmd {
+        fn run(self, session) -> Result<ExtrinsicOutput> {
+            let (genesis, from, options) = match &self.call {
+                CallCmd::Remark {
+                    genesis,
+                    from,
+                    options,
+                    ..
+                }
+                | CallCmd::SetHeapPages {
+                    genesis,
+                    from,
+                    options,
+                    ..
+                }
+                | CallCmd::SetStorage {
+                    genesis,
+                    from,
+                    options,
+                    ..
+                }
+                | CallCmd::KillStorage {
+                    genesis,
+                    from,
+                    options,
+                    ..
+                }
+                | CallCmd::KillPrefix {
+                    genesis,
+                    from,
+                    options,
+                    ..
+                }
+                | CallCmd::SetCode {
+                    genesis,
+                    from,
+                    options,
+                    ..
+                } => (genesis.clone(), from.clone(), options.clone()),
+            };
+
+            create_signed_tx(
+                session.client(genesis.load()?)?,
+                from,
+                NestedCall::from(self.call).into_call()?,
+                options,
+            )
+        }
+    }
+);
+
+// The call as nested into another call, such as `pallet_sudo::sudo`, without
+// any fields related to signing.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum NestedCall {
+    #[serde(rename = "remark")]
+    Remark { remark: SpecBytes },
+    #[serde(rename = "set_heap_pages")]
+    SetHeapPages { pages: u64 },
+    #[serde(rename = "set_storage")]
+    SetStorage { items: Vec<SpecKeyValue> },
+    #[serde(rename = "kill_storage")]
+    KillStorage { keys: Vec<SpecBytes> },
+    #[serde(rename = "kill_prefix")]
+    KillPrefix { prefix: SpecBytes, subkeys: u32 },
+    #[serde(rename = "set_code")]
+    SetCode {
+        code: Option<SpecBytes>,
+        code_file: Option<PathBuf>,
+    },
+}
+
+impl From<CallCmd> for NestedCall {
+    fn from(value: CallCmd) -> Self {
+        match value {
+            CallCmd::Remark { remark, .. } => NestedCall::Remark { remark: remark },
+            CallCmd::SetHeapPages { pages, .. } => NestedCall::SetHeapPages { pages: pages },
+            CallCmd::SetStorage { items, .. } => NestedCall::SetStorage { items: items },
+            CallCmd::KillStorage { keys, .. } => NestedCall::KillStorage { keys: keys },
+            CallCmd::KillPrefix {
+                prefix, subkeys, ..
+            } => NestedCall::KillPrefix {
+                prefix: prefix,
+                subkeys: subkeys,
+            },
+            CallCmd::SetCode {
+                code, code_file, ..
+            } => NestedCall::SetCode {
+                code: code,
+                code_file: code_file,
+            },
+        }
+    }
+}
+
+impl NestedCall {
+    pub fn into_call(self) -> Result<RuntimeCall> {
+        Ok(RuntimeCall::System(match self {
+            NestedCall::Remark { remark } => SystemCall::remark(remark.try_into()?),
+            NestedCall::SetHeapPages { pages } => SystemCall::set_heap_pages(pages),
+            NestedCall::SetStorage { items } => SystemCall::set_storage(
+                items
+                    .into_iter()
+                    .map(|item| item.try_into())
+                    .collect::<Result<Vec<(Vec<u8>, Vec<u8>)>>>()?,
+            ),
+            NestedCall::KillStorage { keys } => SystemCall::kill_storage(
+                keys.into_iter()
+                    .map(|key| key.try_into())
+                    .collect::<Result<Vec<Vec<u8>>>>()?,
+            ),
+            NestedCall::KillPrefix { prefix, subkeys } => {
+                SystemCall::kill_prefix(prefix.try_into()?, subkeys)
+            }
+            NestedCall::SetCode { code, code_file } => {
+                SystemCall::set_code(match (code, code_file) {
+                    (Some(_), Some(_)) => {
+                        return Err(failure::err_msg(
+                            "Either the code or a code file can be specified, not both",
+                        ))
+                    }
+                    (Some(code), None) => code.try_into()?,
+                    (None, Some(path)) => fs::read(path)?,
+                    (None, None) => WASM_BINARY
+                        .ok_or(failure::err_msg("Development wasm binary not available"))?
+                        .to_vec(),
+                })
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::blocks::{BlockCmd, BlockCmdResult};
+    use crate::builder::sudo::PalletSudoCmd;
+    use crate::builder::test_utils::{build_block_in, run_call};
+    use crate::executor::Session;
+    use crate::primitives::runtime::{Block, UncheckedExtrinsic};
+    use crate::primitives::SpecApplyResult;
+    use sp_runtime::traits::Header as HeaderT;
+    use std::convert::TryFrom;
+
+    // Builds a block with the given `frame_system` call, dispatched as root by
+    // the sudo key.
+    fn sudo_block(session: &mut Session, call: &str) -> Block {
+        let extr = match run_call::<PalletSudoCmd>(
+            session,
+            &format!(
+                "sudo: {{ from: alice, call: {{ frame_system: {} }} }}",
+                call
+            ),
+        )
+        .unwrap()
+        {
+            ExtrinsicOutput::Valid(extr) => extr,
+            _ => panic!("Expected a valid extrinsic"),
+        };
+
+        build_block_in(
+            session,
+            &format!(
+                "build: {{ header: {{ digest: {{ logs: [] }} }}, extrinsics: [{}] }}",
+                serde_json::to_string(&extr).unwrap()
+            ),
+        )
+        .unwrap()
+    }
+
+    // Reads the hex encoded storage key at the given block.
+    fn storage(session: &mut Session, block: &Block, key: &str) -> Option<Vec<u8>> {
+        session
+            .client(None)
+            .unwrap()
+            .storage(block.header.hash(), &hex::decode(key).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn storage_manipulation() {
+        let mut session = Session::new();
+        let block = sudo_block(
+            &mut session,
+            r#"{ set_storage: { items: [
+                { key: "0xaabb", value: "0x01" },
+                { key: "0xaacc", value: "0x02" },
+                { key: "0xbb00", value: "0x03" }
+            ] } }"#,
+        );
+        assert_eq!(storage(&mut session, &block, "aabb"), Some(vec![1]));
+        assert_eq!(storage(&mut session, &block, "aacc"), Some(vec![2]));
+        assert_eq!(storage(&mut session, &block, "bb00"), Some(vec![3]));
+
+        let block = sudo_block(&mut session, r#"{ kill_storage: { keys: ["0xbb00"] } }"#);
+        assert_eq!(storage(&mut session, &block, "aabb"), Some(vec![1]));
+        assert_eq!(storage(&mut session, &block, "bb00"), None);
+
+        let block = sudo_block(
+            &mut session,
+            r#"{ kill_prefix: { prefix: "0xaa", subkeys: 2 } }"#,
+        );
+        assert_eq!(storage(&mut session, &block, "aabb"), None);
+        assert_eq!(storage(&mut session, &block, "aacc"), None);
+    }
+
+    #[test]
+    fn default_code() {
+        let mut session = Session::new();
+
+        let extr =
+            match run_call::<FrameSystemCmd>(&mut session, "set_code: { from: alice }").unwrap() {
+                ExtrinsicOutput::Valid(extr) => extr,
+                _ => panic!("Expected a valid extrinsic"),
+            };
+        match UncheckedExtrinsic::try_from(extr).unwrap().function {
+            RuntimeCall::System(SystemCall::set_code(code)) => {
+                assert_eq!(code, WASM_BINARY.unwrap().to_vec())
+            }
+            _ => panic!("Expected a set_code call"),
+        }
+    }
+
+    #[test]
+    fn remark() {
+        let mut session = Session::new();
+
+        let extr = match run_call::<FrameSystemCmd>(
+            &mut session,
+            r#"remark: { from: alice, remark: "0x010203" }"#,
+        )
+        .unwrap()
+        {
+            ExtrinsicOutput::Valid(extr) => extr,
+            _ => panic!("Expected a valid extrinsic"),
+        };
+        let built = match run_call::<BlockCmd>(
+            &mut session,
+            &format!(
+                "build: {{ header: {{ digest: {{ logs: [] }} }}, extrinsics: [{}] }}",
+                serde_json::to_string(&extr).unwrap()
+            ),
+        )
+        .unwrap()
+        {
+            BlockCmdResult::BuildBlock(built) => built,
+            _ => panic!("Expected a single block"),
+        };
+        assert_eq!(built.apply_results, vec![SpecApplyResult::Ok]);
+    }
+}
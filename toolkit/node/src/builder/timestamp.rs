@@ -0,0 +1,163 @@
+use crate::primitives::runtime::{
+    BlockNumber, RuntimeCall, TimestampCall, UncheckedExtrinsic, SLOT_DURATION,
+};
+use crate::primitives::{ExtrinsicOutput, SpecGenesisSource};
+use crate::Result;
+use structopt::StructOpt;
+
+module!(
+    #[serde(rename = "pallet_timestamp")]
+    struct PalletTimestampCmd;
+
+    enum CallCmd {
+        #[serde(rename = "set")]
+        Set {
+            #[structopt(flatten)]
+            #[serde(flatten)]
+            genesis: SpecGenesisSource,
+            // Defaults to the deterministic timestamp of the next block on the
+            // session chain.
+            #[structopt(short, long)]
+            now: Option<u64>,
+        },
+    }
+
+    impl PalletTimestampCmd {
+        fn run(self, session) -> Result<ExtrinsicOutput> {
+            match self.call {
+                CallCmd::Set { genesis, now } => {
+                    let now = match now {
+                        Some(now) => now,
+                        None => {
+                            let (_, number) = session.client(genesis.load()?)?.best_block();
+                            slot_timestamp(number + 1)
+                        }
+                    };
+
+                    Ok(ExtrinsicOutput::Valid(timestamp_inherent(now).into()))
+                }
+            }
+        }
+    }
+);
+
+// The deterministic timestamp of a block, starting from zero at genesis. Each
+// block is placed into its own Aura slot.
+pub fn slot_timestamp(number: BlockNumber) -> u64 {
+    number as u64 * SLOT_DURATION
+}
+
+// Creates the (unsigned) timestamp inherent. No validation is done, so invalid
+// values can be used to test inherent checks.
+pub fn timestamp_inherent(now: u64) -> UncheckedExtrinsic {
+    UncheckedExtrinsic::new_unsigned(RuntimeCall::Timestamp(TimestampCall::set(now)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::test_utils::{build_block_in, run_call};
+    use crate::executor::Session;
+    use crate::primitives::runtime::BlockId;
+    use sp_block_builder::BlockBuilder;
+    use sp_inherents::InherentData;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn block_timestamps() {
+        let mut session = Session::new();
+
+        let mut build = |timestamp: Option<u64>| {
+            build_block_in(
+                &mut session,
+                &format!(
+                    "build: {{ header: {{ digest: {{ logs: [] }} }}, extrinsics: [], timestamp: {} }}",
+                    timestamp.map(|t| t.to_string()).unwrap_or("null".to_string())
+                ),
+            )
+        };
+
+        let block = build(Some(3000)).unwrap();
+        assert_eq!(block.extrinsics[0], timestamp_inherent(3000));
+
+        // Defaults to the block number multiplied by the slot duration.
+        let block = build(None).unwrap();
+        assert_eq!(block.extrinsics[0], timestamp_inherent(12000));
+
+        // Must increase by at least the minimum period.
+        let err = build(Some(12000)).unwrap_err().to_string();
+        assert!(err.starts_with("Timestamp 12000 rejected by the runtime"));
+    }
+
+    #[test]
+    fn set_call() {
+        fn set(session: &mut Session, yaml: &str) -> UncheckedExtrinsic {
+            match run_call::<PalletTimestampCmd>(session, yaml).unwrap() {
+                ExtrinsicOutput::Valid(extr) => UncheckedExtrinsic::try_from(extr).unwrap(),
+                _ => panic!("Expected a valid extrinsic"),
+            }
+        }
+
+        let mut session = Session::new();
+
+        assert_eq!(
+            set(&mut session, "set: { now: 6000 }"),
+            timestamp_inherent(6000)
+        );
+        // Defaults to the timestamp of the next block.
+        assert_eq!(
+            set(&mut session, "set: {}"),
+            timestamp_inherent(slot_timestamp(1))
+        );
+
+        build_block_in(
+            &mut session,
+            "build: { header: { digest: { logs: [] } }, extrinsics: [] }",
+        )
+        .unwrap();
+        assert_eq!(
+            set(&mut session, "set: {}"),
+            timestamp_inherent(slot_timestamp(2))
+        );
+    }
+
+    #[test]
+    fn check_inherents() {
+        let mut session = Session::new();
+
+        // Exceeds the allowed drift of 30 seconds from the current time of the
+        // checking node, which is only verified by `check_inherents`.
+        let block = build_block_in(
+            &mut session,
+            "build: { header: { digest: { logs: [] } }, extrinsics: [], timestamp: 66000 }",
+        )
+        .unwrap();
+
+        let client = session.client(None).unwrap();
+        let check = |now: u64| {
+            let mut data = InherentData::new();
+            data.put_data(sp_timestamp::INHERENT_IDENTIFIER, &now)
+                .unwrap();
+            // The slot of the block, so only the timestamp is checked.
+            data.put_data(
+                sp_consensus_aura::inherents::INHERENT_IDENTIFIER,
+                &(66000 / SLOT_DURATION),
+            )
+            .unwrap();
+
+            client
+                .runtime_api()
+                .check_inherents(&BlockId::Number(0), block.clone(), data)
+                .unwrap()
+        };
+
+        let result = check(6000);
+        assert!(!result.ok());
+        assert!(result
+            .get_error::<sp_timestamp::InherentError>(&sp_timestamp::INHERENT_IDENTIFIER)
+            .unwrap()
+            .is_some());
+
+        assert!(check(66000).ok());
+    }
+}
@@ -9,9 +9,20 @@ pub struct Cli {
 
     #[structopt(parse(from_os_str))]
     pub spec_path: Option<PathBuf>,
+
+    // Shows the decoded form of each result along with the encoded output.
+    #[structopt(long)]
+    pub readable: bool,
+
+    // Chain spec file the chain is started with, unless a task provides its
+    // own genesis.
+    #[structopt(long, parse(from_os_str))]
+    pub genesis_file: Option<PathBuf>,
 }
 
 #[derive(Debug, StructOpt)]
 pub enum Subcommand {
     PalletBalances(builder::PalletBalancesCmd),
+    PalletSudo(builder::PalletSudoCmd),
+    Decode(builder::DecodeCmd),
 }
@@ -15,23 +15,42 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::builder::Builder;
+use crate::builder::{Builder, OutputMode};
 use crate::cli::{Cli, Subcommand};
-use crate::tool_spec::run_tool_spec;
+use crate::executor::Session;
+use crate::primitives::SpecChainSpec;
+use crate::tool_spec::run_tool_spec_file;
 use crate::Result;
-use std::fs;
 use structopt::StructOpt;
 
 /// Parse and run command line arguments
 pub fn run() -> Result<()> {
     let cli = Cli::from_args();
 
+    let mode = if cli.readable {
+        OutputMode::Readable
+    } else {
+        OutputMode::Encoded
+    };
+
+    let genesis = match cli.genesis_file {
+        Some(path) => Some(SpecChainSpec::from_file(&path)?),
+        None => None,
+    };
+
     if let Some(path) = cli.spec_path {
-        run_tool_spec(&fs::read_to_string(path)?)?;
+        run_tool_spec_file(&path, mode, genesis.clone())?;
     }
 
+    let mut session = match genesis {
+        Some(genesis) => Session::with_genesis(genesis),
+        None => Session::new(),
+    };
+
     match cli.subcommand {
-        Some(Subcommand::PalletBalances(cmd)) => cmd.run_and_print()?,
+        Some(Subcommand::PalletBalances(cmd)) => cmd.run_and_print(&mut session, mode)?,
+        Some(Subcommand::PalletSudo(cmd)) => cmd.run_and_print(&mut session, mode)?,
+        Some(Subcommand::Decode(cmd)) => cmd.run_and_print(&mut session, mode)?,
         _ => {}
     };
 
@@ -1,16 +1,31 @@
 use super::Result;
-use crate::builder::{Builder, GenesisCmd};
-use crate::primitives::runtime::{Block, BlockId, RuntimeApi, RuntimeApiImpl};
-use crate::primitives::ChainSpec;
+use crate::builder::genesis::gen_chain_spec_default;
+use crate::primitives::runtime::{
+    AccountId, Block, BlockId, BlockNumber, Header, Index, RuntimeApi, RuntimeApiImpl,
+};
+use crate::primitives::{ChainSpec, SpecChainSpec};
+use frame_system_rpc_runtime_api::AccountNonceApi;
+use log::{LevelFilter, Log, Metadata, Record};
 use sc_client_api::in_mem::Backend;
+use sc_client_api::StorageProvider;
 use sc_executor::native_executor_instance;
 use sc_executor::{NativeExecutor, WasmExecutionMethod};
 use sc_service::client::{new_in_mem, Client, ClientConfig, LocalCallExecutor};
-use sp_api::{ApiRef, ProvideRuntimeApi};
+use sp_api::{ApiRef, Core, ProvideRuntimeApi};
+use sp_blockchain::HeaderBackend;
+use sp_consensus::{BlockImport, BlockImportParams, BlockOrigin, ForkChoiceStrategy, ImportResult};
+use sp_core::storage::StorageKey;
 use sp_core::testing::TaskExecutor;
+use sp_core::H256;
+use sp_runtime::generic::DigestItem;
+use sp_runtime::traits::{Block as BlockT, Header as HeaderT};
 use sp_runtime::BuildStorage;
-use sp_state_machine::InspectState;
-use std::convert::TryFrom;
+use std::cell::RefCell;
+use std::cmp::max;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Once;
 
 // Native executor instance.
 native_executor_instance!(
@@ -29,25 +44,14 @@ type ClientInMemDef = Client<
 
 pub struct ClientInMem {
     client: ClientInMemDef,
+    // The next nonce of each account which signed extrinsics on this chain.
+    nonces: RefCell<HashMap<AccountId, Index>>,
 }
 
 impl ClientInMem {
     pub fn new() -> Result<ClientInMem> {
-        Ok(ClientInMem {
-            client: new_in_mem::<_, Block, _, _>(
-                NativeExecutor::<Executor>::new(WasmExecutionMethod::Interpreted, None, 8),
-                &ChainSpec::try_from(GenesisCmd::default().run()?)?
-                    .build_storage()
-                    .map_err(|_| failure::err_msg("Failed to build temporary chain-spec"))?,
-                None,
-                None,
-                Box::new(TaskExecutor::new()),
-                ClientConfig::default(),
-            )
-            .map_err(|_| failure::err_msg("failed to create in-memory client"))?,
-        })
+        Self::new_with_genesis(gen_chain_spec_default()?)
     }
-    #[allow(dead_code)]
     pub fn new_with_genesis(chain_spec: ChainSpec) -> Result<ClientInMem> {
         Ok(ClientInMem {
             client: new_in_mem::<_, Block, _, _>(
@@ -61,24 +65,253 @@ impl ClientInMem {
                 ClientConfig::default(),
             )
             .map_err(|_| failure::err_msg("failed to create in-memory client"))?,
+            nonces: RefCell::new(HashMap::new()),
         })
     }
-    pub fn exec_context<T, F: FnOnce() -> Result<Option<T>>>(
-        &self,
-        at: &BlockId,
-        f: F,
-    ) -> Result<Option<T>> {
-        let mut res = Ok(None);
+    pub fn runtime_api<'a>(&'a self) -> ApiRef<'a, RuntimeApiImpl<Block, ClientInMemDef>> {
+        self.client.runtime_api()
+    }
+    pub fn best_block(&self) -> (H256, BlockNumber) {
+        let info = self.client.info();
+        (info.best_hash, info.best_number)
+    }
+    pub fn best_block_id(&self) -> BlockId {
+        BlockId::Hash(self.best_block().0)
+    }
+    pub fn block_hash(&self, number: BlockNumber) -> Result<H256> {
+        self.client
+            .hash(number)
+            .map_err(|err| failure::err_msg(format!("Failed to fetch block hash: {}", err)))?
+            .ok_or(failure::err_msg(format!("Block #{} not found", number)))
+    }
+    pub fn block_number(&self, hash: H256) -> Result<BlockNumber> {
+        self.client
+            .number(hash)
+            .map_err(|err| failure::err_msg(format!("Failed to fetch block number: {}", err)))?
+            .ok_or(failure::err_msg(format!("Block {} not found", hash)))
+    }
+    pub fn header(&self, number: BlockNumber) -> Result<Header> {
         self.client
-            .state_at(at)
-            .map_err(|_| failure::err_msg("Failed to set state"))?
-            .inspect_with(|| {
-                res = f();
+            .header(BlockId::Number(number))
+            .map_err(|err| failure::err_msg(format!("Failed to fetch header: {}", err)))?
+            .ok_or(failure::err_msg(format!("Block #{} not found", number)))
+    }
+    pub fn storage(&self, hash: H256, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self
+            .client
+            .storage(&BlockId::Hash(hash), &StorageKey(key.to_vec()))
+            .map_err(|err| failure::err_msg(format!("Failed to fetch storage: {}", err)))?
+            .map(|data| data.0))
+    }
+    // Returns the on-chain nonce of the account at the best block.
+    pub fn account_nonce(&self, account: &AccountId) -> Result<Index> {
+        self.runtime_api()
+            .account_nonce(&self.best_block_id(), account.clone())
+            .map_err(|err| failure::err_msg(format!("Failed to fetch nonce: {}", err)))
+    }
+    // Returns the nonce of the next extrinsic signed by the given account.
+    // This is the highest value of the on-chain nonce and the nonces of
+    // extrinsics previously generated for that account, which might not have
+    // been included yet.
+    pub fn next_nonce(&self, account: &AccountId) -> Result<Index> {
+        let on_chain = self.account_nonce(account)?;
+
+        Ok(self
+            .nonces
+            .borrow()
+            .get(account)
+            .map(|tracked| max(*tracked, on_chain))
+            .unwrap_or(on_chain))
+    }
+    // Keeps track of the nonce used by a generated extrinsic.
+    pub fn track_nonce(&self, account: AccountId, nonce: Index) {
+        let mut nonces = self.nonces.borrow_mut();
+        let tracked = nonces.entry(account).or_insert(0);
+        *tracked = max(*tracked, nonce + 1);
+    }
+    // Overwrites the tracked nonce, e.g. if generated extrinsics were dropped.
+    pub fn set_next_nonce(&self, account: AccountId, nonce: Index) {
+        self.nonces.borrow_mut().insert(account, nonce);
+    }
+    // Executes the block on top of its parent and imports it into the
+    // in-memory backend, making the resulting state available to subsequent
+    // calls.
+    pub fn import_block(&self, block: Block) -> Result<()> {
+        let (mut header, extrinsics) = block.deconstruct();
+        let post_digests = take_seal(&mut header);
+
+        let mut params = BlockImportParams::new(BlockOrigin::Own, header);
+        params.body = Some(extrinsics);
+        params.post_digests = post_digests;
+        params.fork_choice = Some(ForkChoiceStrategy::LongestChain);
+
+        match (&self.client)
+            .import_block(params, HashMap::new())
+            .map_err(|err| failure::err_msg(format!("Failed to import block: {}", err)))?
+        {
+            ImportResult::Imported(_) | ImportResult::AlreadyInChain => Ok(()),
+            ImportResult::KnownBad => Err(failure::err_msg("Block is known to be bad")),
+            ImportResult::UnknownParent => Err(failure::err_msg("Parent of block is unknown")),
+            ImportResult::MissingState => Err(failure::err_msg("Parent state is missing")),
+        }
+    }
+    // Executes the block on top of its parent by calling the runtime directly,
+    // returning the log statements of the runtime. The block is imported
+    // afterwards, so subsequent blocks can be executed on top of it.
+    pub fn execute_block(&self, block: Block) -> Result<Vec<String>> {
+        let at = BlockId::Hash(block.header.parent_hash);
+
+        let mut unsealed = block.clone();
+        take_seal(&mut unsealed.header);
+
+        let (res, logs) = capture_runtime_logs(|| {
+            self.runtime_api()
+                .execute_block(&at, unsealed)
+                .map_err(|err| failure::err_msg(format!("Failed to execute block: {}", err)))
+        })?;
+        res?;
+
+        self.import_block(block)?;
+
+        Ok(logs)
+    }
+}
+
+// The seal is not part of the state transition and gets passed separately, as
+// done by the import queue of the consensus engine.
+fn take_seal(header: &mut Header) -> Vec<DigestItem<H256>> {
+    match header.digest_mut().pop() {
+        Some(seal @ DigestItem::Seal(..)) => vec![seal],
+        Some(item) => {
+            header.digest_mut().push(item);
+            vec![]
+        }
+        None => vec![],
+    }
+}
+
+thread_local! {
+    // Log statements of the runtime, collected while capturing is active on
+    // the current thread.
+    static RUNTIME_LOGS: RefCell<Option<Vec<String>>> = RefCell::new(None);
+}
+
+// Forwards log statements of the runtime (e.g. `print` or `debug::info`) to the
+// capturing thread.
+struct RuntimeLogger;
+
+static RUNTIME_LOGGER: RuntimeLogger = RuntimeLogger;
+
+impl Log for RuntimeLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.target().starts_with("runtime")
+    }
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            RUNTIME_LOGS.with(|logs| {
+                if let Some(logs) = logs.borrow_mut().as_mut() {
+                    logs.push(record.args().to_string());
+                }
             });
+        }
+    }
+    fn flush(&self) {}
+}
 
-        res
+// Set once the runtime logger is installed as the global logger.
+static LOGGER_INSTALLED: AtomicBool = AtomicBool::new(false);
+
+// The number of captures in progress across all threads. The maximum log level
+// is only raised while captures are in progress.
+static ACTIVE_CAPTURES: AtomicUsize = AtomicUsize::new(0);
+
+fn capture_runtime_logs<T, F: FnOnce() -> T>(f: F) -> Result<(T, Vec<String>)> {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        LOGGER_INSTALLED.store(log::set_logger(&RUNTIME_LOGGER).is_ok(), Ordering::SeqCst);
+    });
+
+    // The logs would otherwise go to the other logger and silently be missing
+    // from the output.
+    if !LOGGER_INSTALLED.load(Ordering::SeqCst) {
+        return Err(failure::err_msg(
+            "Failed to capture runtime logs, another logger is already installed",
+        ));
     }
-    pub fn runtime_api<'a>(&'a self) -> ApiRef<'a, RuntimeApiImpl<Block, ClientInMemDef>> {
-        self.client.runtime_api()
+
+    ACTIVE_CAPTURES.fetch_add(1, Ordering::SeqCst);
+    log::set_max_level(LevelFilter::Trace);
+
+    RUNTIME_LOGS.with(|logs| *logs.borrow_mut() = Some(vec![]));
+    let res = f();
+    let logs = RUNTIME_LOGS.with(|logs| logs.borrow_mut().take().unwrap_or_default());
+
+    if ACTIVE_CAPTURES.fetch_sub(1, Ordering::SeqCst) == 1 {
+        log::set_max_level(LevelFilter::Off);
+
+        // Another capture might have started in the meantime.
+        if ACTIVE_CAPTURES.load(Ordering::SeqCst) > 0 {
+            log::set_max_level(LevelFilter::Trace);
+        }
+    }
+
+    Ok((res, logs))
+}
+
+/// The in-memory chain shared by all tasks of a single spec file. The chain is
+/// created lazily on first access, using the default genesis unless a task
+/// provides its own.
+pub struct Session {
+    genesis: Option<SpecChainSpec>,
+    client: Option<ClientInMem>,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Session {
+            genesis: None,
+            client: None,
+        }
+    }
+    // Creates a session whose chain is started on top of the given genesis,
+    // unless a task provides its own.
+    pub fn with_genesis(genesis: SpecChainSpec) -> Self {
+        Session {
+            genesis: Some(genesis),
+            client: None,
+        }
+    }
+    // Returns the session chain. If a genesis is provided which differs from
+    // the genesis the chain was started with, a new chain is started on top
+    // of that genesis, discarding all previously imported blocks.
+    pub fn client(&mut self, genesis: Option<SpecChainSpec>) -> Result<&ClientInMem> {
+        let restart = match (&self.client, &genesis) {
+            (None, _) => true,
+            (Some(_), Some(genesis)) => self.genesis.as_ref() != Some(genesis),
+            (Some(_), None) => false,
+        };
+
+        if restart {
+            // Falls back to the genesis the session was created with.
+            let genesis = genesis.or_else(|| self.genesis.clone());
+
+            self.client = Some(if let Some(chain_spec) = genesis.clone() {
+                ClientInMem::new_with_genesis(chain_spec.try_into()?)
+            } else {
+                ClientInMem::new()
+            }?);
+            self.genesis = genesis;
+        }
+
+        // Is always `Some` at this point.
+        Ok(self.client.as_ref().unwrap())
+    }
+    // Returns the chain spec of the genesis the session chain is running on.
+    pub fn genesis(&self) -> Result<SpecChainSpec> {
+        if let Some(genesis) = &self.genesis {
+            Ok(genesis.clone())
+        } else {
+            gen_chain_spec_default()?.try_into()
+        }
     }
 }
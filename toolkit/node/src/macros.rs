@@ -13,7 +13,7 @@ macro_rules! module {
         }
 
         impl $struct2:ident {
-            fn run($self:ident) -> Result<$ret:ty> $run_body:block
+            fn run($self:ident, $session:ident) -> Result<$ret:ty> $run_body:block
         }
     ) => {
         #[derive(Debug, StructOpt, Serialize, Deserialize)]
@@ -59,7 +59,10 @@ macro_rules! module {
             type Input = $enum;
             type Output = $ret;
 
-            fn run($self) -> crate::Result<Self::Output> $run_body
+            fn run(
+                $self,
+                $session: &mut crate::executor::Session,
+            ) -> crate::Result<Self::Output> $run_body
         }
 
         impl From<$enum> for $struct {
@@ -1,15 +1,22 @@
 use crate::Result;
 use codec::Decode;
 use codec::Encode;
-use runtime::{Block, BlockId, BlockNumber, Header, UncheckedExtrinsic};
-use sc_service::GenericChainSpec;
-use sp_core::crypto::Pair;
-use sp_core::sr25519;
-use sp_core::H256;
+use runtime::{Balance, Block, BlockId, BlockNumber, Header, Index, UncheckedExtrinsic};
+use sc_service::{ChainType, GenericChainSpec, Properties};
+use sp_consensus_aura::sr25519::AuthorityId as AuraId;
+use sp_core::crypto::{Pair, Ss58Codec};
+use sp_core::hashing::{blake2_128, twox_128, twox_64};
+use sp_core::{ed25519, sr25519};
+use sp_core::{Bytes, H256};
+use sp_finality_grandpa::{AuthorityId as GrandpaId, AuthorityWeight};
 use sp_runtime::generic::{Digest, DigestItem};
-use std::collections::HashMap;
+use sp_runtime::transaction_validity::TransactionValidityError;
+use sp_runtime::{ApplyExtrinsicResult, ConsensusEngineId, DispatchError};
+use std::collections::{BTreeMap, HashMap};
 use std::convert::{TryFrom, TryInto};
+use std::fs;
 use std::mem;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use structopt::StructOpt;
 
@@ -43,21 +50,43 @@ pub type SignedExtra = (
 pub mod runtime {
     // `AccountId` -> `sp_runtime::AccountId32`
     pub use toolkit_runtime::{
-        AccountId, Address, AuraConfig, Balance, BalancesConfig, Block, BlockId, BlockNumber,
-        Call as RuntimeCall, CheckedExtrinsic, GenesisConfig, GrandpaConfig, Header, Runtime,
-        RuntimeApi, RuntimeApiImpl, Signature, SignedExtra, SudoConfig, SystemConfig, Timestamp,
-        TimestampCall, UncheckedExtrinsic, WASM_BINARY,
+        AccountId, Address, AuraConfig, AvailableBlockRatio, Balance, BalancesConfig, Block,
+        BlockId, BlockNumber, Call as RuntimeCall, CheckedExtrinsic, ExtrinsicBaseWeight,
+        GenesisConfig, GrandpaConfig, Header, Index, MaximumBlockLength, MaximumBlockWeight,
+        MinimumPeriod, Runtime, RuntimeApi, RuntimeApiImpl, Signature, SignedExtra, SudoConfig,
+        SystemConfig, Timestamp, TimestampCall, UncheckedExtrinsic, SLOT_DURATION, WASM_BINARY,
     };
 }
 
-from_str!(SpecHash, SpecBlockNumber, SpecExtrinsic, SpecAccountSeed,);
+from_str!(
+    SpecHash,
+    SpecBlockNumber,
+    SpecExtrinsic,
+    SpecAccountSeed,
+    SpecAddress,
+    SpecBytes,
+);
 
 pub type ChainSpec = GenericChainSpec<runtime::GenesisConfig>;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct GenericJson(HashMap<String, serde_json::Value>);
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl FromStr for GenericJson {
+    type Err = failure::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(serde_json::from_str(s)?)
+    }
+}
+
+impl From<GenericJson> for Properties {
+    fn from(value: GenericJson) -> Self {
+        value.0.into_iter().collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SpecChainSpec(GenericJson);
 
 impl FromStr for SpecChainSpec {
@@ -80,16 +109,108 @@ impl TryFrom<ChainSpec> for SpecChainSpec {
     }
 }
 
+impl SpecChainSpec {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        SpecChainSpec::from_str(&fs::read_to_string(path)?).map_err(|err| {
+            failure::err_msg(format!(
+                "Failed to read chain spec from {}: {}",
+                path.display(),
+                err
+            ))
+        })
+    }
+}
+
 impl TryFrom<SpecChainSpec> for ChainSpec {
     type Error = failure::Error;
 
     fn try_from(value: SpecChainSpec) -> Result<Self> {
-        ChainSpec::from_json_bytes(serde_json::to_vec(&value.0)?).map_err(|err| {
+        ChainSpec::from_json_bytes(serde_json::to_vec(&value)?).map_err(|err| {
             failure::err_msg(format!("Failed to convert bytes into chain spec: {}", err))
         })
     }
 }
 
+// The genesis a call is run on, specified either inline or as the path to a
+// (raw or non-raw) chain spec file. Defaults to the genesis of the session
+// chain.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, StructOpt)]
+pub struct SpecGenesisSource {
+    #[structopt(short, long)]
+    pub genesis: Option<SpecChainSpec>,
+    #[structopt(long, parse(from_os_str))]
+    pub genesis_file: Option<PathBuf>,
+}
+
+impl SpecGenesisSource {
+    pub fn load(self) -> Result<Option<SpecChainSpec>> {
+        match (self.genesis, self.genesis_file) {
+            (Some(_), Some(_)) => Err(failure::err_msg(
+                "Either a genesis or a genesis file can be specified, not both",
+            )),
+            (Some(genesis), None) => Ok(Some(genesis)),
+            (None, Some(path)) => SpecChainSpec::from_file(&path).map(Some),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+// The format in which a genesis is exported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpecGenesisFormat {
+    // The chain spec as used by Substrate, with the genesis config of each
+    // pallet.
+    Spec,
+    // The chain spec with the genesis as raw storage.
+    Raw,
+    // The raw genesis storage along with the genesis state root and block
+    // hash, as used by Polkadot Host tests. Not a chain spec, see
+    // `SpecHostGenesis`.
+    Host,
+}
+
+impl Default for SpecGenesisFormat {
+    fn default() -> Self {
+        SpecGenesisFormat::Spec
+    }
+}
+
+impl FromStr for SpecGenesisFormat {
+    type Err = failure::Error;
+
+    fn from_str(val: &str) -> Result<Self> {
+        serde_yaml::from_str(val)
+            .map_err(|_| failure::err_msg(format!("Invalid genesis format: {}", val)))
+    }
+}
+
+// The genesis file of Polkadot Host tests. Unlike a raw chain spec, it contains
+// the state root and hash of the genesis block, so hosts can verify the imported
+// state. It's not accepted as genesis of a chain.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpecHostGenesis {
+    pub name: String,
+    pub id: String,
+    pub genesis: SpecHostGenesisRaw,
+    pub genesis_state_root: SpecHash,
+    pub genesis_hash: SpecHash,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpecHostGenesisRaw {
+    pub raw: SpecHostStorage,
+}
+
+// Hex encoded key/value pairs of the top-level storage and of each child trie.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpecHostStorage {
+    pub top: BTreeMap<String, String>,
+    pub children_default: BTreeMap<String, BTreeMap<String, String>>,
+}
+
 // TODO: Those should be generic
 pub type ExtrinsicSigner = sr25519::Pair;
 
@@ -114,7 +235,7 @@ impl SpecAccountSeed {
     }
 }
 
-const SAMPLE_ACCOUNTS: [&'static str; 4] = ["alice", "bob", "dave", "eve"];
+pub const SAMPLE_ACCOUNTS: [&'static str; 4] = ["alice", "bob", "dave", "eve"];
 
 impl TryFrom<SpecAccountSeed> for ExtrinsicSigner {
     type Error = failure::Error;
@@ -134,6 +255,135 @@ impl TryFrom<SpecAccountSeed> for ExtrinsicSigner {
     }
 }
 
+impl TryFrom<SpecAccountSeed> for runtime::AccountId {
+    type Error = failure::Error;
+
+    fn try_from(value: SpecAccountSeed) -> Result<Self> {
+        Ok(ExtrinsicSigner::try_from(value)?.public().into())
+    }
+}
+
+// An account specified as one of the sample accounts (e.g. `alice`), as a hex
+// encoded public key or as a SS58 address.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SpecAddress(String);
+
+impl SpecAddress {
+    // Returns the raw public key of the address. Sample accounts are derived
+    // with the key type of `P`, since Aura and GRANDPA use different schemes.
+    fn raw_public<P: Pair>(&self) -> Result<[u8; 32]> {
+        let lowercase = self.0.to_ascii_lowercase();
+        if SAMPLE_ACCOUNTS.contains(&lowercase.as_str()) {
+            let pair = P::from_string(&format!("//{}", lowercase), None)
+                .map_err(|_| failure::err_msg("Invalid seed phrase"))?;
+            <[u8; 32]>::try_from(pair.public().as_ref())
+                .map_err(|_| failure::err_msg("Public key must be 32 bytes"))
+        } else if self.0.starts_with("0x") {
+            <[u8; 32]>::try_from(hex::decode(&self.0[2..])?.as_slice())
+                .map_err(|_| failure::err_msg("Public key must be 32 bytes"))
+        } else {
+            sr25519::Public::from_ss58check(&self.0)
+                .map(|public| public.0)
+                .map_err(|_| failure::err_msg(format!("Invalid SS58 address: {}", self.0)))
+        }
+    }
+}
+
+impl TryFrom<SpecAddress> for runtime::AccountId {
+    type Error = failure::Error;
+
+    fn try_from(value: SpecAddress) -> Result<Self> {
+        Ok(value.raw_public::<ExtrinsicSigner>()?.into())
+    }
+}
+
+impl TryFrom<SpecAddress> for AuraId {
+    type Error = failure::Error;
+
+    fn try_from(value: SpecAddress) -> Result<Self> {
+        Ok(sr25519::Public::from_raw(value.raw_public::<sr25519::Pair>()?).into())
+    }
+}
+
+impl TryFrom<SpecAddress> for GrandpaId {
+    type Error = failure::Error;
+
+    fn try_from(value: SpecAddress) -> Result<Self> {
+        Ok(ed25519::Public::from_raw(value.raw_public::<ed25519::Pair>()?).into())
+    }
+}
+
+// Units which can be used as suffix of balances, e.g. `1.5 DOT`, along with the
+// amount of decimals of that unit.
+const BALANCE_UNITS: [(&'static str, u32); 3] = [("DOT", 10), ("KSM", 12), ("UNIT", 12)];
+
+// A balance, specified either as a plain number or as a string. Strings can
+// hold the full `u128` range and can be suffixed with a unit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SpecBalance {
+    Number(u64),
+    Text(String),
+}
+
+impl Default for SpecBalance {
+    fn default() -> Self {
+        SpecBalance::Number(0)
+    }
+}
+
+impl FromStr for SpecBalance {
+    type Err = failure::Error;
+
+    fn from_str(val: &str) -> Result<Self> {
+        Ok(SpecBalance::Text(val.to_string()))
+    }
+}
+
+impl TryFrom<SpecBalance> for Balance {
+    type Error = failure::Error;
+
+    fn try_from(val: SpecBalance) -> Result<Self> {
+        let text = match val {
+            SpecBalance::Number(number) => return Ok(number as Balance),
+            SpecBalance::Text(text) => text,
+        };
+
+        let parts: Vec<&str> = text.split_whitespace().collect();
+        let (amount, decimals) = match parts.as_slice() {
+            [amount] => (*amount, 0),
+            [amount, unit] => (
+                *amount,
+                BALANCE_UNITS
+                    .iter()
+                    .find(|(name, _)| name.eq_ignore_ascii_case(unit))
+                    .map(|(_, decimals)| *decimals)
+                    .ok_or(failure::err_msg(format!("Unknown balance unit: {}", unit)))?,
+            ),
+            _ => return Err(failure::err_msg(format!("Invalid balance: {}", text))),
+        };
+
+        let invalid = || failure::err_msg(format!("Invalid balance: {}", text));
+
+        // Split the amount into its integer and fractional part, where the
+        // fractional part cannot have more digits than the unit's decimals.
+        let mut amount_parts = amount.splitn(2, '.');
+        let integer = amount_parts.next().unwrap_or("");
+        let fraction = amount_parts.next().unwrap_or("");
+        if fraction.len() > decimals as usize {
+            return Err(invalid());
+        }
+
+        let digits = format!(
+            "{}{:0<width$}",
+            integer,
+            fraction,
+            width = decimals as usize
+        );
+        digits.parse::<Balance>().map_err(|_| invalid())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RawExtrinsic(String);
 
@@ -143,6 +393,117 @@ impl From<UncheckedExtrinsic> for RawExtrinsic {
     }
 }
 
+impl From<Vec<u8>> for RawExtrinsic {
+    fn from(val: Vec<u8>) -> Self {
+        RawExtrinsic(hex::encode(val))
+    }
+}
+
+impl TryFrom<RawExtrinsic> for UncheckedExtrinsic {
+    type Error = failure::Error;
+
+    fn try_from(val: RawExtrinsic) -> Result<Self> {
+        UncheckedExtrinsic::decode(&mut hex::decode(&val.0)?.as_slice()).map_err(|err| err.into())
+    }
+}
+
+// Options which are available on every call producing a signed extrinsic.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, StructOpt)]
+pub struct SpecTxOptions {
+    // Nonce of the extrinsic. If not specified, the nonce is tracked
+    // automatically for each signer.
+    #[structopt(long)]
+    pub nonce: Option<Index>,
+    // Defaults to an immortal era.
+    #[structopt(long)]
+    pub era: Option<SpecEra>,
+    #[structopt(long)]
+    pub tip: Option<Balance>,
+    // The values below default to the values of the session chain.
+    #[structopt(long)]
+    pub spec_version: Option<u32>,
+    #[structopt(long)]
+    pub tx_version: Option<u32>,
+    #[structopt(long)]
+    pub genesis_hash: Option<SpecHash>,
+    // Makes the extrinsic invalid in the specified way.
+    #[structopt(long)]
+    pub mutate: Option<SpecMutation>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpecMutation {
+    BadSignature,
+    WrongGenesisHash,
+    // A nonce lower than the on-chain nonce.
+    StaleNonce,
+    // A mortal era which is no longer valid at the next block.
+    ExpiredEra,
+    WrongSpecVersion,
+    // The last byte of the encoded extrinsic is missing.
+    TruncatedScale,
+    // The length prefix exceeds the maximum block length. Only decoders of
+    // opaque extrinsics check the prefix, the runtime (sp-runtime 2.0) ignores
+    // it.
+    OversizedLength,
+}
+
+impl FromStr for SpecMutation {
+    type Err = failure::Error;
+
+    fn from_str(val: &str) -> Result<Self> {
+        serde_yaml::from_str(val)
+            .map_err(|_| failure::err_msg(format!("Invalid mutation: {}", val)))
+    }
+}
+
+// The output of calls producing signed extrinsics. Deliberately invalid
+// extrinsics are labeled with the injected invalidity.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ExtrinsicOutput {
+    Valid(RawExtrinsic),
+    Mutated {
+        extrinsic: RawExtrinsic,
+        mutation: SpecMutation,
+    },
+}
+
+// The era of an extrinsic. The birth block of a mortal era defaults to the
+// best block of the session chain. On the command line, the era is specified
+// as `immortal`, `mortal:<period>` or `mortal:<period>:<birth>`.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpecEra {
+    Immortal,
+    Mortal {
+        period: u64,
+        birth: Option<BlockNumber>,
+    },
+}
+
+impl FromStr for SpecEra {
+    type Err = failure::Error;
+
+    fn from_str(val: &str) -> Result<Self> {
+        let parts: Vec<&str> = val.split(':').collect();
+        match parts.as_slice() {
+            ["immortal"] => Ok(SpecEra::Immortal),
+            ["mortal", period] => Ok(SpecEra::Mortal {
+                period: period.parse()?,
+                birth: None,
+            }),
+            ["mortal", period, birth] => Ok(SpecEra::Mortal {
+                period: period.parse()?,
+                birth: Some(birth.parse()?),
+            }),
+            _ => Err(failure::err_msg(format!("Invalid era: {}", val))),
+        }
+    }
+}
+
+// Hex encoded SCALE block, optionally prefixed with `0x`.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct RawBlock(String);
 
@@ -150,7 +511,10 @@ impl FromStr for RawBlock {
     type Err = failure::Error;
 
     fn from_str(val: &str) -> Result<Self> {
-        Ok(RawBlock(String::from_utf8(hex::decode(&val)?)?))
+        let val = val.trim_start_matches("0x");
+        hex::decode(val)?;
+
+        Ok(RawBlock(val.to_string()))
     }
 }
 
@@ -158,7 +522,8 @@ impl TryFrom<RawBlock> for Block {
     type Error = failure::Error;
 
     fn try_from(val: RawBlock) -> Result<Self> {
-        Block::decode(&mut val.0.as_bytes()).map_err(|err| err.into())
+        Block::decode(&mut hex::decode(val.0.trim_start_matches("0x"))?.as_slice())
+            .map_err(|err| err.into())
     }
 }
 
@@ -178,9 +543,55 @@ pub struct SpecTestLayout<T> {
     pub data: T,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpecForkBlock {
+    pub description: String,
+    pub header: Header,
+    pub extrinsics: Vec<Bytes>,
+    pub post_state: H256,
+}
+
+impl SpecForkBlock {
+    pub fn new(description: String, block: Block) -> Self {
+        SpecForkBlock {
+            description: description,
+            post_state: block.header.state_root,
+            extrinsics: block.extrinsics.iter().map(|e| Bytes(e.encode())).collect(),
+            header: block.header,
+        }
+    }
+}
+
+// A branch of a block tree. A branch without a `from` entry starts at the
+// genesis block, otherwise it's built on top of the block of the given branch
+// at index `at` (or the last block of that branch, if not specified).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpecBranch {
+    pub name: String,
+    pub from: Option<String>,
+    pub at: Option<usize>,
+    // One entry per block.
+    pub blocks: Vec<SpecExtrinsicBatch>,
+}
+
+impl FromStr for SpecBranch {
+    type Err = failure::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(serde_json::from_str(s)?)
+    }
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SpecHash(String);
 
+impl From<H256> for SpecHash {
+    fn from(val: H256) -> Self {
+        SpecHash(format!("0x{}", hex::encode(val.as_bytes())))
+    }
+}
+
 impl TryFrom<SpecHash> for H256 {
     type Error = failure::Error;
 
@@ -189,9 +600,277 @@ impl TryFrom<SpecHash> for H256 {
     }
 }
 
+// Hex encoded bytes, optionally prefixed with `0x`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SpecBytes(String);
+
+impl From<Vec<u8>> for SpecBytes {
+    fn from(val: Vec<u8>) -> Self {
+        SpecBytes(format!("0x{}", hex::encode(val)))
+    }
+}
+
+impl SpecBytes {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TryFrom<SpecBytes> for Vec<u8> {
+    type Error = failure::Error;
+
+    fn try_from(val: SpecBytes) -> Result<Self> {
+        Ok(hex::decode(val.0.replace("0x", ""))?)
+    }
+}
+
+// Hashers used for the keys of storage maps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpecHasher {
+    Blake2_128Concat,
+    Twox64Concat,
+    Identity,
+}
+
+impl Default for SpecHasher {
+    fn default() -> Self {
+        SpecHasher::Blake2_128Concat
+    }
+}
+
+impl SpecHasher {
+    pub fn hash(&self, data: &[u8]) -> Vec<u8> {
+        let mut hashed = match self {
+            SpecHasher::Blake2_128Concat => blake2_128(data).to_vec(),
+            SpecHasher::Twox64Concat => twox_64(data).to_vec(),
+            SpecHasher::Identity => vec![],
+        };
+        hashed.extend_from_slice(data);
+        hashed
+    }
+}
+
+// The prefix of a storage item, i.e. `twox128(pallet) ++ twox128(item)`.
+pub fn storage_prefix(pallet: &str, item: &str) -> Vec<u8> {
+    let mut key = twox_128(pallet.as_bytes()).to_vec();
+    key.extend_from_slice(&twox_128(item.as_bytes()));
+    key
+}
+
+// A storage key, either hex encoded or computed from the pallet and item name.
+// Keys of map entries are appended as hex encoded SCALE values, hashed with
+// the given hasher (one per key for double maps).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SpecStorageKey {
+    Raw(SpecBytes),
+    Item {
+        pallet: String,
+        item: String,
+        #[serde(default)]
+        keys: Vec<SpecBytes>,
+        #[serde(default)]
+        hashers: Vec<SpecHasher>,
+    },
+}
+
+impl TryFrom<SpecStorageKey> for Vec<u8> {
+    type Error = failure::Error;
+
+    fn try_from(val: SpecStorageKey) -> Result<Self> {
+        match val {
+            SpecStorageKey::Raw(bytes) => bytes.try_into(),
+            SpecStorageKey::Item {
+                pallet,
+                item,
+                keys,
+                hashers,
+            } => {
+                if hashers.len() > keys.len() {
+                    return Err(failure::err_msg(format!(
+                        "More hashers than keys specified for {}::{}",
+                        pallet, item
+                    )));
+                }
+
+                let mut key = storage_prefix(&pallet, &item);
+                for (index, map_key) in keys.into_iter().enumerate() {
+                    let hasher = hashers.get(index).cloned().unwrap_or_default();
+                    key.extend(hasher.hash(&Vec::<u8>::try_from(map_key)?));
+                }
+
+                Ok(key)
+            }
+        }
+    }
+}
+
+// Inserts or, if the value is omitted, deletes a genesis storage entry. The
+// entry is part of the given child trie or of the top-level trie otherwise.
+// On the command line, it's specified as YAML.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpecStorageEntry {
+    pub child: Option<SpecBytes>,
+    pub key: SpecStorageKey,
+    pub value: Option<SpecBytes>,
+}
+
+impl FromStr for SpecStorageEntry {
+    type Err = failure::Error;
+
+    fn from_str(val: &str) -> Result<Self> {
+        serde_yaml::from_str(val)
+            .map_err(|err| failure::err_msg(format!("Invalid storage entry: {}", err)))
+    }
+}
+
+// An endowed account of a custom genesis. On the command line, it's specified
+// as `<account>=<balance>`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpecAccountBalance {
+    pub account: SpecAddress,
+    pub balance: SpecBalance,
+}
+
+impl FromStr for SpecAccountBalance {
+    type Err = failure::Error;
+
+    fn from_str(val: &str) -> Result<Self> {
+        let parts: Vec<&str> = val.splitn(2, '=').collect();
+        if let [account, balance] = parts.as_slice() {
+            Ok(SpecAccountBalance {
+                account: SpecAddress::from_str(account)?,
+                balance: SpecBalance::from_str(balance)?,
+            })
+        } else {
+            Err(failure::err_msg(format!(
+                "Expected account balance as <account>=<balance>, got: {}",
+                val
+            )))
+        }
+    }
+}
+
+fn default_authority_weight() -> AuthorityWeight {
+    1
+}
+
+// A GRANDPA authority of a custom genesis. On the command line, it's specified
+// as `<account>` or `<account>=<weight>`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpecGrandpaAuthority {
+    pub account: SpecAddress,
+    #[serde(default = "default_authority_weight")]
+    pub weight: AuthorityWeight,
+}
+
+impl FromStr for SpecGrandpaAuthority {
+    type Err = failure::Error;
+
+    fn from_str(val: &str) -> Result<Self> {
+        let parts: Vec<&str> = val.splitn(2, '=').collect();
+        Ok(match parts.as_slice() {
+            [account] => SpecGrandpaAuthority {
+                account: SpecAddress::from_str(account)?,
+                weight: default_authority_weight(),
+            },
+            [account, weight] => SpecGrandpaAuthority {
+                account: SpecAddress::from_str(account)?,
+                weight: weight.parse().map_err(|_| {
+                    failure::err_msg(format!("Invalid authority weight: {}", weight))
+                })?,
+            },
+            _ => unreachable!(),
+        })
+    }
+}
+
+impl TryFrom<SpecGrandpaAuthority> for (GrandpaId, AuthorityWeight) {
+    type Error = failure::Error;
+
+    fn try_from(val: SpecGrandpaAuthority) -> Result<Self> {
+        Ok((val.account.try_into()?, val.weight))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpecChainType {
+    Development,
+    Local,
+    Live,
+    Custom(String),
+}
+
+impl FromStr for SpecChainType {
+    type Err = failure::Error;
+
+    // Unknown names are used as custom chain types.
+    fn from_str(val: &str) -> Result<Self> {
+        Ok(match val.to_ascii_lowercase().as_str() {
+            "development" => SpecChainType::Development,
+            "local" => SpecChainType::Local,
+            "live" => SpecChainType::Live,
+            _ => SpecChainType::Custom(val.to_string()),
+        })
+    }
+}
+
+impl From<SpecChainType> for ChainType {
+    fn from(val: SpecChainType) -> Self {
+        match val {
+            SpecChainType::Development => ChainType::Development,
+            SpecChainType::Local => ChainType::Local,
+            SpecChainType::Live => ChainType::Live,
+            SpecChainType::Custom(name) => ChainType::Custom(name),
+        }
+    }
+}
+
+// On the command line, the key/value pair is specified as `<key>=<value>`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpecKeyValue {
+    pub key: SpecBytes,
+    pub value: SpecBytes,
+}
+
+impl FromStr for SpecKeyValue {
+    type Err = failure::Error;
+
+    fn from_str(val: &str) -> Result<Self> {
+        let parts: Vec<&str> = val.splitn(2, '=').collect();
+        if let [key, value] = parts.as_slice() {
+            Ok(SpecKeyValue {
+                key: SpecBytes::from_str(key)?,
+                value: SpecBytes::from_str(value)?,
+            })
+        } else {
+            Err(failure::err_msg(format!(
+                "Expected key/value pair as <key>=<value>, got: {}",
+                val
+            )))
+        }
+    }
+}
+
+impl TryFrom<SpecKeyValue> for (Vec<u8>, Vec<u8>) {
+    type Error = failure::Error;
+
+    fn try_from(val: SpecKeyValue) -> Result<Self> {
+        Ok((val.key.try_into()?, val.value.try_into()?))
+    }
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SpecBlockNumber(String);
 
+impl From<BlockNumber> for SpecBlockNumber {
+    fn from(val: BlockNumber) -> Self {
+        SpecBlockNumber(format!("0x{:x}", val))
+    }
+}
+
 impl TryFrom<SpecBlockNumber> for BlockNumber {
     type Error = failure::Error;
 
@@ -215,22 +894,165 @@ impl TryFrom<SpecExtrinsic> for UncheckedExtrinsic {
     }
 }
 
+// The extrinsics of a single block. On the command line, the extrinsics are
+// separated by commas.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SpecExtrinsicBatch(Vec<SpecExtrinsic>);
+
+impl SpecExtrinsicBatch {
+    pub fn into_inner(self) -> Vec<SpecExtrinsic> {
+        self.0
+    }
+}
+
+impl FromStr for SpecExtrinsicBatch {
+    type Err = failure::Error;
+
+    fn from_str(val: &str) -> Result<Self> {
+        Ok(SpecExtrinsicBatch(
+            val.split(',')
+                .map(|e| e.trim())
+                .filter(|e| !e.is_empty())
+                .map(|e| SpecExtrinsic::from_str(e))
+                .collect::<Result<Vec<SpecExtrinsic>>>()?,
+        ))
+    }
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize, StructOpt)]
 pub struct SpecBlock {
-    #[structopt(short, long)]
-    pub genesis: Option<SpecChainSpec>,
+    #[structopt(flatten)]
+    #[serde(flatten)]
+    pub genesis: SpecGenesisSource,
     #[structopt(flatten)]
     pub header: SpecHeader,
     #[structopt(short, long)]
     pub extrinsics: Vec<SpecExtrinsic>,
+    // Value of the timestamp inherent. Defaults to the block number multiplied
+    // by the slot duration.
+    #[structopt(short, long)]
+    pub timestamp: Option<u64>,
+    // Skips the generation of the Aura slot digest and the seal.
+    #[structopt(long)]
+    #[serde(default)]
+    pub unsealed: bool,
+    #[structopt(long, default_value = "dispatch_error")]
+    #[serde(default)]
+    pub fail_on: SpecFailOn,
+    // Appends generated transfers after the specified extrinsics until the
+    // block weight or length is exhausted.
+    #[structopt(long)]
+    #[serde(default)]
+    pub fill_block: bool,
+    // Signer and destination of the generated transfers. Default to alice and
+    // bob respectively.
+    #[structopt(long)]
+    pub fill_from: Option<SpecAccountSeed>,
+    #[structopt(long)]
+    pub fill_to: Option<SpecAddress>,
+}
+
+// Which extrinsic apply results fail the block build. Invalid extrinsics which
+// do not fail the build are left out of the block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpecFailOn {
+    // Fails on invalid extrinsics.
+    Invalid,
+    // Fails on invalid extrinsics and on dispatch errors.
+    DispatchError,
+    Never,
+}
+
+impl Default for SpecFailOn {
+    fn default() -> Self {
+        SpecFailOn::DispatchError
+    }
+}
+
+impl FromStr for SpecFailOn {
+    type Err = failure::Error;
+
+    fn from_str(val: &str) -> Result<Self> {
+        serde_yaml::from_str(val)
+            .map_err(|_| failure::err_msg(format!("Invalid failure policy: {}", val)))
+    }
+}
+
+// The result of applying an extrinsic, as returned by the runtime.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpecApplyResult {
+    Ok,
+    DispatchError {
+        // Set for errors of modules.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        module_index: Option<u8>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error_index: Option<u8>,
+        message: String,
+    },
+    Invalid {
+        message: String,
+    },
+    Unknown {
+        message: String,
+    },
+}
+
+impl SpecApplyResult {
+    pub fn is_invalid(&self) -> bool {
+        match self {
+            SpecApplyResult::Invalid { .. } | SpecApplyResult::Unknown { .. } => true,
+            _ => false,
+        }
+    }
+    pub fn is_dispatch_error(&self) -> bool {
+        match self {
+            SpecApplyResult::DispatchError { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+impl From<ApplyExtrinsicResult> for SpecApplyResult {
+    fn from(val: ApplyExtrinsicResult) -> Self {
+        match val {
+            Ok(Ok(())) => SpecApplyResult::Ok,
+            Ok(Err(err)) => {
+                let (module_index, error_index) = match &err {
+                    DispatchError::Module { index, error, .. } => (Some(*index), Some(*error)),
+                    _ => (None, None),
+                };
+
+                SpecApplyResult::DispatchError {
+                    module_index: module_index,
+                    error_index: error_index,
+                    message: <&'static str>::from(err).to_string(),
+                }
+            }
+            Err(TransactionValidityError::Invalid(err)) => SpecApplyResult::Invalid {
+                message: <&'static str>::from(err).to_string(),
+            },
+            Err(TransactionValidityError::Unknown(err)) => SpecApplyResult::Unknown {
+                message: <&'static str>::from(err).to_string(),
+            },
+        }
+    }
 }
 
 impl SpecBlock {
-    // Convert relevant fields into runtime native types.
+    // Convert relevant fields into runtime native types. The parent hash must
+    // be set at this point.
     pub fn prep(mut self) -> Result<(BlockId, Header, Vec<UncheckedExtrinsic>)> {
         // Convert into runtime types.
-        let at =
-            BlockId::Number(BlockNumber::try_from(self.header.number.clone())?.saturating_sub(1));
+        let at = BlockId::Hash(
+            self.header
+                .parent_hash
+                .clone()
+                .ok_or(failure::err_msg("Parent hash of block not specified"))?
+                .try_into()?,
+        );
         let header = mem::take(&mut self.header).try_into()?;
         let extrinsics = mem::take(&mut self.extrinsics)
             .into_iter()
@@ -259,16 +1081,150 @@ impl TryFrom<SpecBlock> for Block {
 #[derive(Debug, Clone, Default, Serialize, Deserialize, StructOpt)]
 pub struct SpecHeader {
     #[structopt(short, long)]
-    pub parent_hash: SpecHash,
+    pub parent_hash: Option<SpecHash>,
     #[structopt(short, long)]
-    pub number: SpecBlockNumber,
+    pub number: Option<SpecBlockNumber>,
     #[structopt(flatten)]
     pub digest: SpecDigest,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize, StructOpt)]
 pub struct SpecDigest {
-    pub logs: Vec<String>,
+    pub logs: Vec<SpecDigestItem>,
+}
+
+// A digest item, either as a hex encoded SCALE `DigestItem` or structured, e.g.
+// `pre_runtime: { engine: aura, data: "0x..." }`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SpecDigestItem {
+    Encoded(String),
+    Item(SpecDigestItemKind),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpecDigestItemKind {
+    PreRuntime {
+        engine: SpecEngineId,
+        data: SpecBytes,
+    },
+    Seal {
+        engine: SpecEngineId,
+        data: SpecBytes,
+    },
+    Consensus {
+        engine: SpecEngineId,
+        data: SpecBytes,
+    },
+    ChangesTrieRoot(SpecHash),
+    Other(SpecBytes),
+}
+
+impl FromStr for SpecDigestItem {
+    type Err = failure::Error;
+
+    fn from_str(val: &str) -> Result<Self> {
+        if val.starts_with("0x") {
+            Ok(SpecDigestItem::Encoded(val.to_string()))
+        } else {
+            Ok(serde_yaml::from_str(val)?)
+        }
+    }
+}
+
+impl TryFrom<SpecDigestItem> for DigestItem<H256> {
+    type Error = failure::Error;
+
+    fn try_from(val: SpecDigestItem) -> Result<Self> {
+        let item = match val {
+            SpecDigestItem::Encoded(encoded) => {
+                return Ok(DigestItem::decode(
+                    &mut hex::decode(encoded.replace("0x", ""))?.as_slice(),
+                )?)
+            }
+            SpecDigestItem::Item(item) => item,
+        };
+
+        Ok(match item {
+            SpecDigestItemKind::PreRuntime { engine, data } => {
+                DigestItem::PreRuntime(engine.try_into()?, data.try_into()?)
+            }
+            SpecDigestItemKind::Seal { engine, data } => {
+                DigestItem::Seal(engine.try_into()?, data.try_into()?)
+            }
+            SpecDigestItemKind::Consensus { engine, data } => {
+                DigestItem::Consensus(engine.try_into()?, data.try_into()?)
+            }
+            SpecDigestItemKind::ChangesTrieRoot(root) => {
+                DigestItem::ChangesTrieRoot(root.try_into()?)
+            }
+            SpecDigestItemKind::Other(data) => DigestItem::Other(data.try_into()?),
+        })
+    }
+}
+
+impl From<DigestItem<H256>> for SpecDigestItem {
+    fn from(val: DigestItem<H256>) -> Self {
+        SpecDigestItem::Item(match val {
+            DigestItem::PreRuntime(engine, data) => SpecDigestItemKind::PreRuntime {
+                engine: engine.into(),
+                data: data.into(),
+            },
+            DigestItem::Seal(engine, data) => SpecDigestItemKind::Seal {
+                engine: engine.into(),
+                data: data.into(),
+            },
+            DigestItem::Consensus(engine, data) => SpecDigestItemKind::Consensus {
+                engine: engine.into(),
+                data: data.into(),
+            },
+            DigestItem::ChangesTrieRoot(root) => SpecDigestItemKind::ChangesTrieRoot(root.into()),
+            DigestItem::Other(data) => SpecDigestItemKind::Other(data.into()),
+            // Items without a structured representation are kept encoded.
+            item => return SpecDigestItem::Encoded(format!("0x{}", hex::encode(item.encode()))),
+        })
+    }
+}
+
+// Known consensus engines by name (`aura`, `babe`, `grandpa`) or any other four
+// character identifier.
+const ENGINE_IDS: [(&'static str, ConsensusEngineId); 3] = [
+    ("aura", *b"aura"),
+    ("babe", *b"BABE"),
+    ("grandpa", *b"FRNK"),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpecEngineId(String);
+
+impl From<ConsensusEngineId> for SpecEngineId {
+    fn from(val: ConsensusEngineId) -> Self {
+        SpecEngineId(
+            ENGINE_IDS
+                .iter()
+                .find(|(_, id)| *id == val)
+                .map(|(name, _)| name.to_string())
+                .unwrap_or(String::from_utf8_lossy(&val).to_string()),
+        )
+    }
+}
+
+impl TryFrom<SpecEngineId> for ConsensusEngineId {
+    type Error = failure::Error;
+
+    fn try_from(val: SpecEngineId) -> Result<Self> {
+        if let Some((_, id)) = ENGINE_IDS
+            .iter()
+            .find(|(name, _)| *name == val.0.to_ascii_lowercase())
+        {
+            return Ok(*id);
+        }
+
+        <[u8; 4]>::try_from(val.0.as_bytes()).map_err(|_| {
+            failure::err_msg(format!("Invalid consensus engine identifier: {}", val.0))
+        })
+    }
 }
 
 impl TryFrom<SpecHeader> for Header {
@@ -276,8 +1232,14 @@ impl TryFrom<SpecHeader> for Header {
 
     fn try_from(val: SpecHeader) -> Result<Self> {
         Ok(Header {
-            parent_hash: val.parent_hash.try_into()?,
-            number: val.number.try_into()?,
+            parent_hash: val
+                .parent_hash
+                .ok_or(failure::err_msg("Parent hash of header not specified"))?
+                .try_into()?,
+            number: val
+                .number
+                .ok_or(failure::err_msg("Number of header not specified"))?
+                .try_into()?,
             state_root: SpecHash::from_str(
                 "0x0000000000000000000000000000000000000000000000000000000000000000",
             )?
@@ -290,13 +1252,34 @@ impl TryFrom<SpecHeader> for Header {
                 logs: val
                     .digest
                     .logs
-                    .iter()
-                    .map(|d| hex::decode(d.replace("0x", "")).map_err(|err| err.into()))
-                    .collect::<Result<Vec<Vec<u8>>>>()?
-                    .iter_mut()
-                    .map(|d| DigestItem::decode(&mut d.as_slice()).map_err(|err| err.into()))
+                    .into_iter()
+                    .map(|item| item.try_into())
                     .collect::<Result<Vec<DigestItem<H256>>>>()?,
             },
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn balance_units() {
+        let parse = |val: &str| Balance::try_from(SpecBalance::from_str(val).unwrap());
+
+        assert_eq!(parse("100").unwrap(), 100);
+        assert_eq!(
+            parse("340282366920938463463374607431768211455").unwrap(),
+            u128::MAX
+        );
+        assert_eq!(parse("1 DOT").unwrap(), 10_000_000_000);
+        assert_eq!(parse("1.5 dot").unwrap(), 15_000_000_000);
+        assert_eq!(parse("0.000000000001 KSM").unwrap(), 1);
+        assert_eq!(parse("2 UNIT").unwrap(), 2_000_000_000_000);
+        assert!(parse("0.00000000001 DOT").is_err());
+        assert!(parse("1 ETH").is_err());
+        assert!(parse("1.5").is_err());
+        assert!(parse("1 DOT extra").is_err());
+    }
+}
@@ -0,0 +1,953 @@
+use crate::Result;
+use serde_yaml::Value;
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+
+// Expressions as used by templates and the `when:` keyword, such as
+// `result is defined and result | length > 0`. Supported are literals
+// (numbers, strings, `true`, `false`, `null`), lists (`[1, 2]`), variables
+// (`var.name[0]`), arithmetic (`+`, `-`, `*`, `/`, `%`), string concatenation
+// (`~`), comparisons (`==`, `!=`, `<`, `<=`, `>`, `>=`, `in`, `not in`),
+// boolean operators (`and`, `or`, `not`), parentheses, function calls
+// (`blake2_256(data)`), the `is defined` and `is not defined` tests and
+// filters (`length`, `hex`, `first`, `to_json` and `default(value)`).
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum PathSegment {
+    Name(String),
+    Index(usize),
+}
+
+// Resolves the variables and functions of an expression.
+pub trait Lookup {
+    // Returns `None` if the variable is not defined.
+    fn lookup(&self, path: &[PathSegment]) -> Option<Value>;
+    fn call(&self, name: &str, _args: Vec<Value>) -> Result<Value> {
+        Err(failure::err_msg(format!("Unknown function: {}", name)))
+    }
+}
+
+pub fn parse(input: &str) -> Result<Expr> {
+    Parser::new(input)?.parse()
+}
+
+// Evaluates the expression and returns its value.
+pub fn evaluate<L: Lookup>(input: &str, vars: &L) -> Result<Value> {
+    parse(input)?.evaluate(vars)
+}
+
+// Evaluates the expression and returns whether its value is truthy.
+pub fn evaluate_condition<L: Lookup>(input: &str, vars: &L) -> Result<bool> {
+    evaluate(input, vars).map(|value| is_truthy(&value))
+}
+
+// `false`, `null`, zero and empty strings, sequences and mappings are falsy,
+// anything else is truthy.
+pub fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(boolean) => *boolean,
+        Value::Number(number) => number.as_f64().map(|n| n != 0.0).unwrap_or(true),
+        Value::String(string) => !string.is_empty(),
+        Value::Sequence(seq) => !seq.is_empty(),
+        Value::Mapping(map) => !map.is_empty(),
+    }
+}
+
+// Converts the value into text, as inserted into strings. Sequences and
+// mappings are converted into JSON.
+pub fn to_text(value: &Value) -> Result<String> {
+    Ok(match value {
+        Value::Null => String::new(),
+        Value::Bool(boolean) => boolean.to_string(),
+        Value::Number(number) => number.to_string(),
+        Value::String(string) => string.clone(),
+        Value::Sequence(_) | Value::Mapping(_) => serde_json::to_string(value)?,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Literal(Value),
+    Symbol(&'static str),
+}
+
+// Symbols with two characters come first, so those are preferred.
+const SYMBOLS: [&'static str; 19] = [
+    "==", "!=", "<=", ">=", "<", ">", "(", ")", "[", "]", ".", "|", ",", "+", "-", "*", "/", "%",
+    "~",
+];
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = vec![];
+    let mut pos = 0;
+
+    while pos < chars.len() {
+        let c = chars[pos];
+
+        if c.is_whitespace() {
+            pos += 1;
+        } else if c.is_ascii_digit() {
+            let start = pos;
+            while pos < chars.len() && (chars[pos].is_ascii_digit() || chars[pos] == '.') {
+                pos += 1;
+            }
+
+            let number: String = chars[start..pos].iter().collect();
+            match serde_yaml::from_str::<Value>(&number)? {
+                value @ Value::Number(_) => tokens.push(Token::Literal(value)),
+                _ => return Err(failure::err_msg(format!("Invalid number: {}", number))),
+            }
+        } else if c == '"' || c == '\'' {
+            let mut string = String::new();
+            pos += 1;
+
+            loop {
+                match chars.get(pos) {
+                    Some('\\') if pos + 1 < chars.len() => {
+                        string.push(chars[pos + 1]);
+                        pos += 2;
+                    }
+                    Some(quote) if *quote == c => break,
+                    Some(other) => {
+                        string.push(*other);
+                        pos += 1;
+                    }
+                    None => {
+                        return Err(failure::err_msg(format!(
+                            "Unterminated string in expression: {}",
+                            input
+                        )))
+                    }
+                }
+            }
+
+            pos += 1;
+            tokens.push(Token::Literal(Value::String(string)));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = pos;
+            while pos < chars.len() && (chars[pos].is_alphanumeric() || chars[pos] == '_') {
+                pos += 1;
+            }
+
+            tokens.push(Token::Ident(chars[start..pos].iter().collect()));
+        } else {
+            let rest: String = chars[pos..].iter().take(2).collect();
+            let symbol = SYMBOLS
+                .iter()
+                .find(|symbol| rest.starts_with(*symbol))
+                .ok_or(failure::err_msg(format!(
+                    "Unexpected character '{}' in expression: {}",
+                    c, input
+                )))?;
+
+            pos += symbol.len();
+            tokens.push(Token::Symbol(symbol));
+        }
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    In,
+    NotIn,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArithOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Value),
+    Variable(Vec<PathSegment>),
+    List(Vec<Expr>),
+    Call(String, Vec<Expr>),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(CompareOp, Box<Expr>, Box<Expr>),
+    // Whether the variable is defined, or not defined if negated.
+    Defined(Vec<PathSegment>, bool),
+    Neg(Box<Expr>),
+    Arith(ArithOp, Box<Expr>, Box<Expr>),
+    Concat(Box<Expr>, Box<Expr>),
+    // The filter name along with its arguments.
+    Filter(Box<Expr>, String, Vec<Expr>),
+}
+
+struct Path<'a>(&'a [PathSegment]);
+
+impl<'a> fmt::Display for Path<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, segment) in self.0.iter().enumerate() {
+            match segment {
+                PathSegment::Name(name) if index == 0 => write!(f, "{}", name)?,
+                PathSegment::Name(name) => write!(f, ".{}", name)?,
+                PathSegment::Index(i) => write!(f, "[{}]", i)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Expr {
+    pub fn evaluate<L: Lookup>(&self, vars: &L) -> Result<Value> {
+        Ok(match self {
+            Expr::Literal(value) => value.clone(),
+            Expr::Variable(path) => vars.lookup(path).ok_or(failure::err_msg(format!(
+                "Variable \"{}\" not found",
+                Path(path)
+            )))?,
+            Expr::List(items) => Value::Sequence(
+                items
+                    .iter()
+                    .map(|item| item.evaluate(vars))
+                    .collect::<Result<Vec<Value>>>()?,
+            ),
+            Expr::Call(name, args) => vars.call(
+                name,
+                args.iter()
+                    .map(|arg| arg.evaluate(vars))
+                    .collect::<Result<Vec<Value>>>()?,
+            )?,
+            Expr::Not(expr) => Value::Bool(!is_truthy(&expr.evaluate(vars)?)),
+            // Both operators short-circuit, so the right side may rely on the
+            // left side, e.g. `var is defined and var > 0`.
+            Expr::And(left, right) => {
+                Value::Bool(is_truthy(&left.evaluate(vars)?) && is_truthy(&right.evaluate(vars)?))
+            }
+            Expr::Or(left, right) => {
+                Value::Bool(is_truthy(&left.evaluate(vars)?) || is_truthy(&right.evaluate(vars)?))
+            }
+            Expr::Compare(op, left, right) => {
+                Value::Bool(compare(*op, &left.evaluate(vars)?, &right.evaluate(vars)?)?)
+            }
+            Expr::Defined(path, negated) => Value::Bool(vars.lookup(path).is_some() != *negated),
+            Expr::Neg(expr) => arithmetic(
+                ArithOp::Sub,
+                &Value::Number(0.into()),
+                &expr.evaluate(vars)?,
+            )?,
+            Expr::Arith(op, left, right) => {
+                arithmetic(*op, &left.evaluate(vars)?, &right.evaluate(vars)?)?
+            }
+            Expr::Concat(left, right) => Value::String(format!(
+                "{}{}",
+                to_text(&left.evaluate(vars)?)?,
+                to_text(&right.evaluate(vars)?)?
+            )),
+            // Applies to undefined variables, which can't be evaluated.
+            Expr::Filter(expr, name, args) if name == "default" => {
+                if args.len() != 1 {
+                    return Err(failure::err_msg("Filter `default` expects one argument"));
+                }
+
+                let value = match &**expr {
+                    Expr::Variable(path) => vars.lookup(path),
+                    expr => Some(expr.evaluate(vars)?),
+                };
+
+                match value {
+                    Some(value) => value,
+                    None => args[0].evaluate(vars)?,
+                }
+            }
+            Expr::Filter(expr, name, args) => apply_filter(
+                name,
+                expr.evaluate(vars)?,
+                args.iter()
+                    .map(|arg| arg.evaluate(vars))
+                    .collect::<Result<Vec<Value>>>()?,
+            )?,
+        })
+    }
+}
+
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(number) => number.as_f64(),
+        _ => as_integer(value).map(|value| value as f64),
+    }
+}
+
+// Integers, including strings of digits, as used for balances exceeding the
+// number range of YAML. Strings of digits are treated as integers everywhere,
+// i.e. in arithmetic, comparisons and filters.
+fn as_integer(value: &Value) -> Option<i128> {
+    match value {
+        Value::Number(number) => number
+            .as_u64()
+            .map(i128::from)
+            .or(number.as_i64().map(i128::from)),
+        Value::String(string)
+            if !string.is_empty() && string.chars().all(|c| c.is_ascii_digit()) =>
+        {
+            string.parse().ok()
+        }
+        _ => None,
+    }
+}
+
+fn from_integer(value: i128) -> Value {
+    if let Ok(value) = u64::try_from(value) {
+        Value::Number(value.into())
+    } else if let Ok(value) = i64::try_from(value) {
+        Value::Number(value.into())
+    } else {
+        Value::String(value.to_string())
+    }
+}
+
+// Integers are calculated exactly, where division truncates. Any other numbers
+// are calculated as floats. Other strings and sequences can be added to each
+// other.
+fn arithmetic(op: ArithOp, left: &Value, right: &Value) -> Result<Value> {
+    if let (Some(l), Some(r)) = (as_integer(left), as_integer(right)) {
+        return match op {
+            ArithOp::Add => l.checked_add(r),
+            ArithOp::Sub => l.checked_sub(r),
+            ArithOp::Mul => l.checked_mul(r),
+            ArithOp::Div => l.checked_div(r),
+            ArithOp::Rem => l.checked_rem(r),
+        }
+        .map(from_integer)
+        .ok_or(failure::err_msg(format!(
+            "Arithmetic overflow or division by zero: {:?} {:?} {:?}",
+            left, op, right
+        )));
+    }
+
+    match (op, left, right) {
+        (ArithOp::Add, Value::String(left), Value::String(right)) => {
+            return Ok(Value::String(format!("{}{}", left, right)))
+        }
+        (ArithOp::Add, Value::Sequence(left), Value::Sequence(right)) => {
+            return Ok(Value::Sequence(left.iter().chain(right).cloned().collect()))
+        }
+        _ => {}
+    }
+
+    match (as_number(left), as_number(right)) {
+        (Some(l), Some(r)) => Ok(Value::Number(
+            match op {
+                ArithOp::Add => l + r,
+                ArithOp::Sub => l - r,
+                ArithOp::Mul => l * r,
+                ArithOp::Div => l / r,
+                ArithOp::Rem => l % r,
+            }
+            .into(),
+        )),
+        _ => Err(failure::err_msg(format!(
+            "Cannot apply {:?} to {:?} and {:?}",
+            op, left, right
+        ))),
+    }
+}
+
+// Numbers are compared by value, so `1 == 1.0` and `'100' == 100`.
+fn values_equal(left: &Value, right: &Value) -> bool {
+    if let (Some(left), Some(right)) = (as_integer(left), as_integer(right)) {
+        return left == right;
+    }
+
+    match (as_number(left), as_number(right)) {
+        (Some(left), Some(right)) => left == right,
+        _ => left == right,
+    }
+}
+
+fn compare(op: CompareOp, left: &Value, right: &Value) -> Result<bool> {
+    let ordering = || -> Result<Ordering> {
+        if let (Some(l), Some(r)) = (as_integer(left), as_integer(right)) {
+            return Ok(l.cmp(&r));
+        }
+
+        match (left, right) {
+            (Value::String(left), Value::String(right)) => Ok(left.cmp(right)),
+            _ => match (as_number(left), as_number(right)) {
+                (Some(l), Some(r)) => l.partial_cmp(&r),
+                _ => None,
+            }
+            .ok_or(failure::err_msg(format!(
+                "Cannot compare {:?} with {:?}",
+                left, right
+            ))),
+        }
+    };
+
+    let contains = || -> Result<bool> {
+        match (left, right) {
+            (_, Value::Sequence(seq)) => Ok(seq.iter().any(|item| values_equal(left, item))),
+            (_, Value::Mapping(map)) => Ok(map.contains_key(left)),
+            (Value::String(left), Value::String(right)) => Ok(right.contains(left.as_str())),
+            _ => Err(failure::err_msg(format!(
+                "Cannot check whether {:?} is in {:?}",
+                left, right
+            ))),
+        }
+    };
+
+    Ok(match op {
+        CompareOp::Eq => values_equal(left, right),
+        CompareOp::Ne => !values_equal(left, right),
+        CompareOp::Lt => ordering()? == Ordering::Less,
+        CompareOp::Le => ordering()? != Ordering::Greater,
+        CompareOp::Gt => ordering()? == Ordering::Greater,
+        CompareOp::Ge => ordering()? != Ordering::Less,
+        CompareOp::In => contains()?,
+        CompareOp::NotIn => !contains()?,
+    })
+}
+
+fn apply_filter(name: &str, value: Value, args: Vec<Value>) -> Result<Value> {
+    if !args.is_empty() {
+        return Err(failure::err_msg(format!(
+            "Filter `{}` does not expect arguments",
+            name
+        )));
+    }
+
+    match name {
+        "length" => {
+            let length = match &value {
+                Value::String(string) => string.chars().count(),
+                Value::Sequence(seq) => seq.len(),
+                Value::Mapping(map) => map.len(),
+                _ => {
+                    return Err(failure::err_msg(format!(
+                        "Value has no length: {:?}",
+                        value
+                    )))
+                }
+            };
+
+            Ok(Value::Number((length as u64).into()))
+        }
+        // Integers are encoded big-endian in whole bytes, other strings as UTF-8
+        // bytes and sequences as individual bytes. SCALE encoded values are
+        // created with `scale_encode`.
+        "hex" => {
+            let bytes = match &value {
+                Value::String(_) | Value::Number(_) if as_integer(&value).is_some() => {
+                    let number = as_integer(&value)
+                        .and_then(|number| u128::try_from(number).ok())
+                        .ok_or(failure::err_msg(format!("Cannot hex encode {:?}", value)))?;
+
+                    let bytes = number.to_be_bytes();
+                    let skip = bytes.iter().take_while(|byte| **byte == 0).count();
+                    // Zero is encoded as a single byte.
+                    bytes[skip.min(bytes.len() - 1)..].to_vec()
+                }
+                Value::String(string) => string.as_bytes().to_vec(),
+                Value::Sequence(seq) => seq
+                    .iter()
+                    .map(|byte| {
+                        as_integer(byte)
+                            .and_then(|byte| u8::try_from(byte).ok())
+                            .ok_or(failure::err_msg(format!("Invalid byte: {:?}", byte)))
+                    })
+                    .collect::<Result<Vec<u8>>>()?,
+                _ => return Err(failure::err_msg(format!("Cannot hex encode {:?}", value))),
+            };
+
+            Ok(Value::String(format!("0x{}", hex::encode(bytes))))
+        }
+        "first" => match &value {
+            Value::Sequence(seq) => seq
+                .first()
+                .cloned()
+                .ok_or(failure::err_msg("Cannot take first item of empty sequence")),
+            Value::String(string) => string
+                .chars()
+                .next()
+                .map(|c| Value::String(c.to_string()))
+                .ok_or(failure::err_msg("Cannot take first item of empty string")),
+            _ => Err(failure::err_msg(format!("Value has no items: {:?}", value))),
+        },
+        "to_json" => Ok(Value::String(serde_json::to_string(&value)?)),
+        _ => Err(failure::err_msg(format!("Unknown filter: {}", name))),
+    }
+}
+
+struct Parser {
+    input: String,
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(input: &str) -> Result<Self> {
+        Ok(Parser {
+            input: input.to_string(),
+            tokens: tokenize(input)?,
+            pos: 0,
+        })
+    }
+    fn parse(mut self) -> Result<Expr> {
+        let expr = self.parse_or()?;
+
+        if self.pos < self.tokens.len() {
+            return Err(self.unexpected());
+        }
+
+        Ok(expr)
+    }
+    fn unexpected(&self) -> failure::Error {
+        match self.tokens.get(self.pos) {
+            Some(token) => failure::err_msg(format!(
+                "Unexpected {:?} in expression: {}",
+                token, self.input
+            )),
+            None => failure::err_msg(format!("Unexpected end of expression: {}", self.input)),
+        }
+    }
+    fn peek_keyword(&self, offset: usize, keyword: &str) -> bool {
+        match self.tokens.get(self.pos + offset) {
+            Some(Token::Ident(ident)) => ident == keyword,
+            _ => false,
+        }
+    }
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.peek_keyword(0, keyword);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+    fn eat_symbol(&mut self, symbol: &str) -> bool {
+        let found = match self.tokens.get(self.pos) {
+            Some(Token::Symbol(found)) => *found == symbol,
+            _ => false,
+        };
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+    fn expect_symbol(&mut self, symbol: &str) -> Result<()> {
+        if self.eat_symbol(symbol) {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+    fn parse_or(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_and()?;
+        while self.eat_keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+    fn parse_and(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_not()?;
+        while self.eat_keyword("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_not()?));
+        }
+        Ok(expr)
+    }
+    fn parse_not(&mut self) -> Result<Expr> {
+        if self.eat_keyword("not") {
+            Ok(Expr::Not(Box::new(self.parse_not()?)))
+        } else {
+            self.parse_comparison()
+        }
+    }
+    fn parse_comparison(&mut self) -> Result<Expr> {
+        let left = self.parse_concat()?;
+
+        if self.eat_keyword("is") {
+            let negated = self.eat_keyword("not");
+            if !self.eat_keyword("defined") {
+                return Err(self.unexpected());
+            }
+
+            return match left {
+                Expr::Variable(path) => Ok(Expr::Defined(path, negated)),
+                _ => Err(failure::err_msg(format!(
+                    "Only variables can be tested with `is defined`: {}",
+                    self.input
+                ))),
+            };
+        }
+
+        let op = if self.eat_keyword("in") {
+            CompareOp::In
+        } else if self.peek_keyword(0, "not") && self.peek_keyword(1, "in") {
+            self.pos += 2;
+            CompareOp::NotIn
+        } else {
+            let op = match self.tokens.get(self.pos) {
+                Some(Token::Symbol("==")) => CompareOp::Eq,
+                Some(Token::Symbol("!=")) => CompareOp::Ne,
+                Some(Token::Symbol("<")) => CompareOp::Lt,
+                Some(Token::Symbol("<=")) => CompareOp::Le,
+                Some(Token::Symbol(">")) => CompareOp::Gt,
+                Some(Token::Symbol(">=")) => CompareOp::Ge,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            op
+        };
+
+        Ok(Expr::Compare(
+            op,
+            Box::new(left),
+            Box::new(self.parse_concat()?),
+        ))
+    }
+    fn parse_concat(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_additive()?;
+        while self.eat_symbol("~") {
+            expr = Expr::Concat(Box::new(expr), Box::new(self.parse_additive()?));
+        }
+        Ok(expr)
+    }
+    fn parse_additive(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_multiplicative()?;
+        loop {
+            let op = if self.eat_symbol("+") {
+                ArithOp::Add
+            } else if self.eat_symbol("-") {
+                ArithOp::Sub
+            } else {
+                return Ok(expr);
+            };
+            expr = Expr::Arith(op, Box::new(expr), Box::new(self.parse_multiplicative()?));
+        }
+    }
+    fn parse_multiplicative(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_unary()?;
+        loop {
+            let op = if self.eat_symbol("*") {
+                ArithOp::Mul
+            } else if self.eat_symbol("/") {
+                ArithOp::Div
+            } else if self.eat_symbol("%") {
+                ArithOp::Rem
+            } else {
+                return Ok(expr);
+            };
+            expr = Expr::Arith(op, Box::new(expr), Box::new(self.parse_unary()?));
+        }
+    }
+    fn parse_unary(&mut self) -> Result<Expr> {
+        if self.eat_symbol("-") {
+            Ok(Expr::Neg(Box::new(self.parse_unary()?)))
+        } else {
+            self.parse_filtered()
+        }
+    }
+    fn parse_filtered(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_primary()?;
+        while self.eat_symbol("|") {
+            let name = match self.tokens.get(self.pos).cloned() {
+                Some(Token::Ident(name)) => name,
+                _ => return Err(self.unexpected()),
+            };
+            self.pos += 1;
+
+            let args = if self.eat_symbol("(") {
+                self.parse_args()?
+            } else {
+                vec![]
+            };
+
+            expr = Expr::Filter(Box::new(expr), name, args);
+        }
+        Ok(expr)
+    }
+    // Parses the arguments of a call, following the opening parenthesis.
+    fn parse_args(&mut self) -> Result<Vec<Expr>> {
+        let mut args = vec![];
+        if !self.eat_symbol(")") {
+            loop {
+                args.push(self.parse_or()?);
+                if self.eat_symbol(")") {
+                    break;
+                }
+                self.expect_symbol(",")?;
+            }
+        }
+        Ok(args)
+    }
+    fn parse_primary(&mut self) -> Result<Expr> {
+        let token = self.tokens.get(self.pos).cloned();
+
+        match token {
+            Some(Token::Literal(value)) => {
+                self.pos += 1;
+                Ok(Expr::Literal(value))
+            }
+            Some(Token::Symbol("(")) => {
+                self.pos += 1;
+                let expr = self.parse_or()?;
+                self.expect_symbol(")")?;
+                Ok(expr)
+            }
+            Some(Token::Symbol("[")) => {
+                self.pos += 1;
+                let mut items = vec![];
+                if !self.eat_symbol("]") {
+                    loop {
+                        items.push(self.parse_or()?);
+                        if self.eat_symbol("]") {
+                            break;
+                        }
+                        self.expect_symbol(",")?;
+                    }
+                }
+                Ok(Expr::List(items))
+            }
+            Some(Token::Ident(ident)) => {
+                self.pos += 1;
+                match ident.as_str() {
+                    "true" => return Ok(Expr::Literal(Value::Bool(true))),
+                    "false" => return Ok(Expr::Literal(Value::Bool(false))),
+                    "null" | "none" => return Ok(Expr::Literal(Value::Null)),
+                    _ => {}
+                }
+
+                if self.eat_symbol("(") {
+                    return Ok(Expr::Call(ident, self.parse_args()?));
+                }
+
+                let mut path = vec![PathSegment::Name(ident)];
+                loop {
+                    if self.eat_symbol(".") {
+                        match self.tokens.get(self.pos).cloned() {
+                            Some(Token::Ident(name)) => path.push(PathSegment::Name(name)),
+                            _ => return Err(self.unexpected()),
+                        }
+                        self.pos += 1;
+                    } else if self.eat_symbol("[") {
+                        match self.tokens.get(self.pos).and_then(|token| match token {
+                            Token::Literal(Value::Number(number)) => number.as_u64(),
+                            _ => None,
+                        }) {
+                            Some(index) => path.push(PathSegment::Index(index as usize)),
+                            None => return Err(self.unexpected()),
+                        }
+                        self.pos += 1;
+                        self.expect_symbol("]")?;
+                    } else {
+                        break;
+                    }
+                }
+
+                Ok(Expr::Variable(path))
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Vars(Value);
+
+    impl Lookup for Vars {
+        fn lookup(&self, path: &[PathSegment]) -> Option<Value> {
+            let mut value = &self.0;
+            for segment in path {
+                value = match segment {
+                    PathSegment::Name(name) => value.get(name.as_str())?,
+                    PathSegment::Index(index) => value.get(*index)?,
+                };
+            }
+            Some(value.clone())
+        }
+        fn call(&self, name: &str, args: Vec<Value>) -> Result<Value> {
+            match name {
+                "sum" => Ok(Value::Number(
+                    args.iter()
+                        .filter_map(|arg| arg.as_u64())
+                        .sum::<u64>()
+                        .into(),
+                )),
+                _ => Err(failure::err_msg(format!("Unknown function: {}", name))),
+            }
+        }
+    }
+
+    fn eval(input: &str) -> Result<bool> {
+        let vars = Vars(
+            serde_yaml::from_str(
+                r#"
+                balance: 100
+                name: alice
+                results: [ok, ok, invalid]
+                info: { age: 33, job: { employer: CorpA } }
+                empty: []
+            "#,
+            )
+            .unwrap(),
+        );
+
+        evaluate_condition(input, &vars)
+    }
+
+    #[test]
+    fn comparisons() {
+        assert!(eval("balance == 100").unwrap());
+        assert!(eval("balance == 100.0").unwrap());
+        assert!(eval("balance != 99").unwrap());
+        assert!(eval("balance > 99 and balance >= 100").unwrap());
+        assert!(eval("balance < 101 and balance <= 100").unwrap());
+        assert!(eval("name == 'alice'").unwrap());
+        assert!(eval("name < \"bob\"").unwrap());
+        assert!(eval("info.age == 33").unwrap());
+        assert!(eval("info.job.employer == 'CorpA'").unwrap());
+        assert!(eval("results[2] == 'invalid'").unwrap());
+        assert!(eval("'invalid' in results").unwrap());
+        assert!(eval("'dispatch_error' not in results").unwrap());
+        assert!(eval("'age' in info").unwrap());
+        assert!(eval("'lic' in name").unwrap());
+
+        // Strings of digits are compared as integers.
+        assert!(eval("balance == '100'").unwrap());
+        assert!(eval("'100' in [100]").unwrap());
+        assert!(eval("'9' < '10' and balance < '1000000000000000000000000'").unwrap());
+
+        assert!(!eval("balance == 101").unwrap());
+        assert!(eval("name > 1").is_err());
+    }
+
+    #[test]
+    fn boolean_operators() {
+        assert!(eval("true and not false").unwrap());
+        assert!(eval("false or balance == 100").unwrap());
+        assert!(eval("not (balance == 100 and name == 'bob')").unwrap());
+        assert!(!eval("balance == 100 and name == 'bob'").unwrap());
+        // `and` binds stronger than `or`.
+        assert!(eval("true or false and false").unwrap());
+        assert!(eval("name").unwrap());
+        assert!(!eval("empty").unwrap());
+    }
+
+    #[test]
+    fn defined_and_length() {
+        assert!(eval("balance is defined").unwrap());
+        assert!(eval("missing is not defined").unwrap());
+        assert!(eval("info.job is defined and info.salary is not defined").unwrap());
+        // Short-circuits, so the undefined variable is not evaluated.
+        assert!(!eval("missing is defined and missing > 0").unwrap());
+        assert!(eval("missing > 0").is_err());
+
+        assert!(eval("results | length == 3").unwrap());
+        assert!(eval("name | length == 5").unwrap());
+        assert!(eval("empty | length == 0").unwrap());
+        assert!(eval("balance | length == 3").is_err());
+        assert!(eval("name | unknown").is_err());
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        assert!(eval("balance ==").is_err());
+        assert!(eval("(balance == 100").is_err());
+        assert!(eval("balance == 100 100").is_err());
+        assert!(eval("name == 'alice").is_err());
+        assert!(eval("balance # 100").is_err());
+        assert!(eval("'a' is defined").is_err());
+        assert!(eval("name | default(").is_err());
+        assert!(eval("balance +").is_err());
+    }
+
+    fn value(input: &str) -> Value {
+        let vars = Vars(
+            serde_yaml::from_str(
+                r#"
+                balance: 100
+                name: alice
+                bytes: [1, 2, 255]
+                results: [ok, invalid]
+            "#,
+            )
+            .unwrap(),
+        );
+
+        evaluate(input, &vars).unwrap()
+    }
+
+    fn yaml(input: &str) -> Value {
+        serde_yaml::from_str(input).unwrap()
+    }
+
+    #[test]
+    fn arithmetic_and_concatenation() {
+        assert_eq!(value("balance + 50"), yaml("150"));
+        assert_eq!(value("balance - 150"), yaml("-50"));
+        assert_eq!(value("1 + 2 * 3"), yaml("7"));
+        assert_eq!(value("(1 + 2) * 3"), yaml("9"));
+        assert_eq!(value("balance / 3"), yaml("33"));
+        assert_eq!(value("balance % 3"), yaml("1"));
+        assert_eq!(value("-balance"), yaml("-100"));
+        assert_eq!(value("balance / 8.0"), yaml("12.5"));
+        assert_eq!(
+            value("1000000000000 * 1000000000000"),
+            yaml("'1000000000000000000000000'")
+        );
+        assert_eq!(
+            value("'1000000000000000000000000' + 1"),
+            yaml("'1000000000000000000000001'")
+        );
+        assert_eq!(value("name + '//stash'"), yaml("alice//stash"));
+        // Strings of digits are integers.
+        assert_eq!(value("'12' + '3'"), yaml("15"));
+        assert_eq!(value("'12' - 3"), yaml("9"));
+        assert_eq!(value("'12' ~ '3'"), yaml("'123'"));
+        assert_eq!(value("'1' + 0.5"), yaml("1.5"));
+        assert_eq!(value("'//' ~ name ~ balance"), yaml("//alice100"));
+        assert_eq!(value("results + ['ok']"), yaml("[ok, invalid, ok]"));
+
+        let vars = Vars(Value::Null);
+        assert!(evaluate("1 / 0", &vars).is_err());
+        assert!(evaluate("'a' - 1", &vars).is_err());
+    }
+
+    #[test]
+    fn filters() {
+        assert_eq!(value("name | hex"), yaml("'0x616c696365'"));
+        assert_eq!(value("balance | hex"), yaml("'0x64'"));
+        assert_eq!(value("5 | hex"), yaml("'0x05'"));
+        assert_eq!(value("0 | hex"), yaml("'0x00'"));
+        assert_eq!(value("256 | hex"), yaml("'0x0100'"));
+        assert_eq!(value("'4096' | hex"), yaml("'0x1000'"));
+        assert_eq!(value("bytes | hex"), yaml("'0x0102ff'"));
+        assert_eq!(value("results | first"), yaml("ok"));
+        assert_eq!(value("results | first | length"), yaml("2"));
+        assert_eq!(value("results | to_json"), yaml(r#"'["ok","invalid"]'"#));
+        assert_eq!(value("missing | default(5) + 1"), yaml("6"));
+        assert_eq!(value("balance | default(5)"), yaml("100"));
+        assert_eq!(value("missing.name | default(name)"), yaml("alice"));
+        assert_eq!(value("sum(balance, 1, 2) * 2"), yaml("206"));
+        assert_eq!(value("sum() | default(1)"), yaml("0"));
+
+        let vars = Vars(Value::Null);
+        assert!(evaluate("unknown(1)", &vars).is_err());
+        assert!(evaluate("[] | first", &vars).is_err());
+        assert!(evaluate("(0 - 1) | hex", &vars).is_err());
+        assert!(evaluate("1 | length(2)", &vars).is_err());
+        assert!(evaluate("missing | default", &vars).is_err());
+    }
+}
@@ -0,0 +1,287 @@
+use crate::primitives::runtime::{AccountId, Balance, Block};
+use crate::primitives::{
+    RawBlock, SpecAddress, SpecBalance, SpecBytes, SpecHasher, SpecStorageKey,
+};
+use crate::Result;
+use codec::{Compact, Encode};
+use serde_yaml::Value;
+use sp_core::crypto::Ss58Codec;
+use sp_core::hashing::{blake2_256, twox_128};
+use sp_core::H256;
+use sp_runtime::traits::Header as HeaderT;
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+const FUNCTIONS: [&'static str; 7] = [
+    "blake2_256",
+    "twox_128",
+    "storage_key",
+    "account_id",
+    "ss58",
+    "scale_encode",
+    "block_hash",
+];
+
+// Functions available in templates, such as `{{ blake2_256('data') }}`. Bytes
+// are returned hex encoded and accepted as hex encoded strings (prefixed with
+// `0x`), as lists of numbers or as plain strings, which are encoded as UTF-8.
+pub fn call(name: &str, args: Vec<Value>) -> Result<Value> {
+    match (name, args.as_slice()) {
+        ("blake2_256", [data]) => Ok(hex_value(blake2_256(&bytes(data)?).to_vec())),
+        ("twox_128", [data]) => Ok(hex_value(twox_128(&bytes(data)?).to_vec())),
+        ("storage_key", [pallet, item, rest @ ..]) if rest.len() <= 2 => {
+            storage_key(pallet, item, rest.get(0), rest.get(1))
+        }
+        ("account_id", [account]) => Ok(hex_value(account_id(account)?.encode())),
+        ("ss58", [account]) => Ok(Value::String(account_id(account)?.to_ss58check())),
+        ("scale_encode", [ty, value]) => Ok(hex_value(scale_encode(ty, value)?)),
+        ("block_hash", [block]) => block_hash(block),
+        _ if FUNCTIONS.iter().any(|function| *function == name) => Err(failure::err_msg(format!(
+            "Invalid number of arguments for function `{}`",
+            name
+        ))),
+        _ => Err(failure::err_msg(format!("Unknown function: {}", name))),
+    }
+}
+
+fn hex_value(bytes: Vec<u8>) -> Value {
+    Value::String(format!("0x{}", hex::encode(bytes)))
+}
+
+fn string(value: &Value) -> Result<&str> {
+    value
+        .as_str()
+        .ok_or(failure::err_msg(format!("Expected a string: {:?}", value)))
+}
+
+fn bytes(value: &Value) -> Result<Vec<u8>> {
+    match value {
+        Value::String(string) if string.starts_with("0x") => {
+            Vec::<u8>::try_from(SpecBytes::from_str(string)?)
+        }
+        Value::String(string) => Ok(string.as_bytes().to_vec()),
+        Value::Sequence(_) => Ok(serde_yaml::from_value(value.clone())?),
+        _ => Err(failure::err_msg(format!(
+            "Expected bytes, found: {:?}",
+            value
+        ))),
+    }
+}
+
+// Accepts sample accounts (e.g. `alice`), hex encoded public keys and SS58
+// addresses.
+fn account_id(value: &Value) -> Result<AccountId> {
+    AccountId::try_from(SpecAddress::from_str(string(value)?)?)
+}
+
+fn integer(value: &Value) -> Result<u128> {
+    match value {
+        Value::Number(number) => number.as_u64().map(u128::from),
+        // Numbers exceeding the number range of YAML.
+        Value::String(string) => string.parse().ok(),
+        _ => None,
+    }
+    .ok_or(failure::err_msg(format!(
+        "Expected an unsigned integer, found: {:?}",
+        value
+    )))
+}
+
+// The keys of the storage map are either a single key or a list of keys, each
+// hashed with the corresponding hasher (`blake2_128_concat` by default).
+// Accounts are converted into their account ID.
+fn storage_key(
+    pallet: &Value,
+    item: &Value,
+    keys: Option<&Value>,
+    hashers: Option<&Value>,
+) -> Result<Value> {
+    let keys = match keys {
+        Some(Value::Sequence(keys)) => keys.iter().collect(),
+        Some(key) => vec![key],
+        None => vec![],
+    };
+
+    let hashers = match hashers {
+        Some(hashers) => serde_yaml::from_value::<Vec<SpecHasher>>(hashers.clone())?,
+        None => vec![],
+    };
+
+    let key = SpecStorageKey::Item {
+        pallet: string(pallet)?.to_string(),
+        item: string(item)?.to_string(),
+        keys: keys
+            .into_iter()
+            .map(|key| {
+                let bytes = match account_id(key) {
+                    Ok(account) => account.encode(),
+                    Err(_) => bytes(key)?,
+                };
+
+                Ok(SpecBytes::from(bytes))
+            })
+            .collect::<Result<Vec<SpecBytes>>>()?,
+        hashers: hashers,
+    };
+
+    Ok(hex_value(Vec::<u8>::try_from(key)?))
+}
+
+// Supported types are `u8`, `u16`, `u32`, `u64`, `u128`, `bool`, `compact`,
+// `balance`, `account_id`, `hash` and `bytes`.
+fn scale_encode(ty: &Value, value: &Value) -> Result<Vec<u8>> {
+    let out_of_range = || failure::err_msg(format!("Value out of range: {:?}", value));
+
+    Ok(match string(ty)? {
+        "u8" => u8::try_from(integer(value)?)
+            .map_err(|_| out_of_range())?
+            .encode(),
+        "u16" => u16::try_from(integer(value)?)
+            .map_err(|_| out_of_range())?
+            .encode(),
+        "u32" => u32::try_from(integer(value)?)
+            .map_err(|_| out_of_range())?
+            .encode(),
+        "u64" => u64::try_from(integer(value)?)
+            .map_err(|_| out_of_range())?
+            .encode(),
+        "u128" => integer(value)?.encode(),
+        "bool" => value
+            .as_bool()
+            .ok_or(failure::err_msg(format!("Expected a boolean: {:?}", value)))?
+            .encode(),
+        "compact" => Compact(integer(value)?).encode(),
+        "balance" => {
+            Balance::try_from(serde_yaml::from_value::<SpecBalance>(value.clone())?)?.encode()
+        }
+        "account_id" => account_id(value)?.encode(),
+        "hash" => H256::from_slice(
+            &<[u8; 32]>::try_from(bytes(value)?.as_slice())
+                .map_err(|_| failure::err_msg("Hash must be 32 bytes"))?,
+        )
+        .encode(),
+        "bytes" => bytes(value)?.encode(),
+        ty => return Err(failure::err_msg(format!("Unsupported type: {}", ty))),
+    })
+}
+
+// Accepts the hex encoded block or the result of the block builder.
+fn block_hash(value: &Value) -> Result<Value> {
+    let raw = match value.get("block") {
+        Some(block) => block,
+        None => value,
+    };
+
+    let block = Block::try_from(RawBlock::from_str(string(raw)?)?)?;
+    Ok(hex_value(block.header.hash().as_bytes().to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::storage_prefix;
+
+    fn call_str(name: &str, args: &[&str]) -> Result<Value> {
+        call(
+            name,
+            args.iter()
+                .map(|arg| serde_yaml::from_str(arg).unwrap())
+                .collect(),
+        )
+    }
+
+    fn hex_str(bytes: &[u8]) -> Value {
+        hex_value(bytes.to_vec())
+    }
+
+    #[test]
+    fn hashes() {
+        assert_eq!(
+            call_str("blake2_256", &["data"]).unwrap(),
+            hex_str(&blake2_256(b"data"))
+        );
+        assert_eq!(
+            call_str("blake2_256", &["'0x0102'"]).unwrap(),
+            hex_str(&blake2_256(&[1, 2]))
+        );
+        assert_eq!(
+            call_str("twox_128", &["[1, 2]"]).unwrap(),
+            hex_str(&twox_128(&[1, 2]))
+        );
+        assert!(call_str("twox_128", &["data", "data"]).is_err());
+        assert!(call_str("unknown", &[]).is_err());
+    }
+
+    #[test]
+    fn accounts() {
+        let alice = AccountId::try_from(SpecAddress::from_str("alice").unwrap()).unwrap();
+
+        assert_eq!(
+            call_str("account_id", &["alice"]).unwrap(),
+            hex_str(&alice.encode())
+        );
+        assert_eq!(
+            call_str("ss58", &["alice"]).unwrap(),
+            Value::String(alice.to_ss58check())
+        );
+        assert_eq!(
+            call_str("ss58", &[&alice.to_ss58check()]).unwrap(),
+            Value::String(alice.to_ss58check())
+        );
+        assert!(call_str("account_id", &["unknown"]).is_err());
+    }
+
+    #[test]
+    fn storage_keys() {
+        let alice = AccountId::try_from(SpecAddress::from_str("alice").unwrap()).unwrap();
+
+        let mut expected = storage_prefix("System", "Account");
+        expected.extend(SpecHasher::Blake2_128Concat.hash(&alice.encode()));
+        assert_eq!(
+            call_str("storage_key", &["System", "Account", "alice"]).unwrap(),
+            hex_str(&expected)
+        );
+
+        let mut expected = storage_prefix("System", "BlockHash");
+        expected.extend(SpecHasher::Twox64Concat.hash(&1u32.encode()));
+        assert_eq!(
+            call_str(
+                "storage_key",
+                &["System", "BlockHash", "['0x01000000']", "[twox64_concat]"]
+            )
+            .unwrap(),
+            hex_str(&expected)
+        );
+
+        assert_eq!(
+            call_str("storage_key", &["Timestamp", "Now"]).unwrap(),
+            hex_str(&storage_prefix("Timestamp", "Now"))
+        );
+    }
+
+    #[test]
+    fn scale_encoding() {
+        assert_eq!(
+            call_str("scale_encode", &["u32", "1"]).unwrap(),
+            hex_str(&1u32.encode())
+        );
+        assert_eq!(
+            call_str("scale_encode", &["u128", "'1000000000000000000000000'"]).unwrap(),
+            hex_str(&1_000_000_000_000_000_000_000_000u128.encode())
+        );
+        assert_eq!(
+            call_str("scale_encode", &["compact", "100"]).unwrap(),
+            hex_str(&Compact(100u128).encode())
+        );
+        assert_eq!(
+            call_str("scale_encode", &["bytes", "'0x0102'"]).unwrap(),
+            hex_str(&vec![1u8, 2].encode())
+        );
+        assert_eq!(
+            call_str("scale_encode", &["bool", "true"]).unwrap(),
+            hex_str(&true.encode())
+        );
+        assert!(call_str("scale_encode", &["u8", "256"]).is_err());
+        assert!(call_str("scale_encode", &["unknown", "1"]).is_err());
+    }
+}
@@ -1,41 +1,184 @@
-use crate::builder::{BlockCmd, GenesisCmd, PalletBalancesCmd};
+use crate::builder::{
+    BlockCmd, DecodeCmd, ForkCmd, FrameSystemCmd, GenesisCmd, OutputMode, PalletBalancesCmd,
+    PalletSudoCmd, PalletTimestampCmd,
+};
 
+use crate::primitives::SpecChainSpec;
 use crate::Result;
 use processor::{Processor, Task};
 
 use std::cmp::PartialEq;
 use std::hash::Hash;
+use std::path::Path;
 
+mod expression;
+mod functions;
 mod processor;
+mod template;
 pub use processor::{Mapper, TaskOutcome};
 
 mapping!(
     PalletBalances => PalletBalancesCmd,
     Block => BlockCmd,
     Genesis => GenesisCmd,
+    Fork => ForkCmd,
+    PalletSudo => PalletSudoCmd,
+    FrameSystem => FrameSystemCmd,
+    PalletTimestamp => PalletTimestampCmd,
+    Decode => DecodeCmd,
 );
 
 pub fn run_tool_spec(yaml: &str) -> Result<()> {
-    Processor::<Mapping>::new(yaml)?.process()
+    run_tool_spec_with_output(yaml, OutputMode::Encoded, None)
+}
+
+// Runs the spec, optionally on top of the given genesis instead of the one set
+// in the spec.
+pub fn run_tool_spec_with_output(
+    yaml: &str,
+    mode: OutputMode,
+    genesis: Option<SpecChainSpec>,
+) -> Result<()> {
+    run_processor(Processor::new(yaml)?, mode, genesis)
+}
+
+// Runs the spec file, where included files are relative to the spec file.
+pub fn run_tool_spec_file(
+    path: &Path,
+    mode: OutputMode,
+    genesis: Option<SpecChainSpec>,
+) -> Result<()> {
+    run_processor(Processor::from_file(path)?, mode, genesis)
+}
+
+fn run_processor(
+    processor: Processor<Mapping>,
+    mode: OutputMode,
+    genesis: Option<SpecChainSpec>,
+) -> Result<()> {
+    let mut processor = processor.with_output_mode(mode);
+    if let Some(genesis) = genesis {
+        processor = processor.with_genesis(genesis);
+    }
+
+    processor.process()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::builder::test_utils::{build_block_in, run_call, TempDir};
+    use crate::executor::Session;
+    use std::convert::TryFrom;
+    use std::fs;
 
     #[test]
     fn build_block() {
-        run_tool_spec(r#"
+        run_tool_spec(
+            r#"
             - name: Build block
               block:
                 build:
                   header:
-                    parent_hash: "0x0000000000000000000000000000000000000000000000000000000000000000"
                     number: "0x1"
                     digest:
                       logs: []
                   extrinsics: []
-        "#).unwrap();
+        "#,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn build_blocks_on_session_chain() {
+        run_tool_spec(
+            r#"
+            - name: Build first block
+              block:
+                build:
+                  header:
+                    digest:
+                      logs: []
+                  extrinsics: []
+
+            - name: Build second block on top of the first
+              block:
+                build:
+                  header:
+                    number: "0x2"
+                    digest:
+                      logs: []
+                  extrinsics: []
+
+            - name: Balance transfer at the best block
+              pallet_balances:
+                transfer:
+                  from: alice
+                  to: bob
+                  balance: 100
+        "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn build_chain() {
+        run_tool_spec(
+            r#"
+            - name: Build chain of empty blocks
+              block:
+                build_chain:
+                  count: 3
+
+            - name: Continue chain
+              block:
+                build_chain:
+                  extrinsics:
+                    - []
+                    - []
+        "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn fork() {
+        run_tool_spec(
+            r#"
+            - name: Transfer of the main branch
+              pallet_balances:
+                transfer:
+                  from: alice
+                  to: bob
+                  balance: 100
+              register: main_transfers
+
+            - name: Transfer of the side branch
+              pallet_balances:
+                transfer:
+                  from: bob
+                  to: dave
+                  balance: 200
+              register: side_transfers
+
+            - name: Build fork
+              fork:
+                build:
+                  name: Fork Awareness
+                  description: Multiple blocks were produced on the same parent block
+                  branches:
+                    - name: main
+                      blocks:
+                        - []
+                        - "{{ main_transfers }}"
+                    - name: side
+                      from: main
+                      at: 0
+                      blocks:
+                        - "{{ side_transfers }}"
+        "#,
+        )
+        .unwrap()
     }
 
     #[test]
@@ -53,6 +196,282 @@ mod tests {
         .unwrap()
     }
 
+    #[test]
+    fn pallet_balances_calls() {
+        run_tool_spec(
+            r#"
+            - name: Transfer to SS58 address
+              pallet_balances:
+                transfer:
+                  from: alice
+                  to: 5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY
+                  balance: 1.5 DOT
+
+            - name: Transfer to public key
+              pallet_balances:
+                transfer_keep_alive:
+                  from: alice
+                  to: "0xd43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d"
+                  balance: "340282366920938463463374607431768211455"
+
+            - name: Root calls
+              pallet_sudo:
+                sudo:
+                  from: alice
+                  call: "{{ item }}"
+              loop:
+                - pallet_balances:
+                    set_balance:
+                      who: bob
+                      new_free: 10 KSM
+                - pallet_balances:
+                    force_transfer:
+                      source: bob
+                      to: dave
+                      balance: 1 UNIT
+        "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn pallet_sudo() {
+        run_tool_spec(
+            r#"
+            - name: Sudo balance transfer
+              pallet_sudo:
+                sudo:
+                  from: alice
+                  call:
+                    pallet_balances:
+                      transfer:
+                        to: bob
+                        balance: 100
+
+            - name: Sudo with unchecked weight
+              pallet_sudo:
+                sudo_unchecked_weight:
+                  from: alice
+                  call:
+                    pallet_balances:
+                      transfer:
+                        to: bob
+                        balance: 100
+                  weight: 1000
+
+            - name: Sudo as bob
+              pallet_sudo:
+                sudo_as:
+                  from: alice
+                  who: bob
+                  call:
+                    pallet_sudo:
+                      set_key:
+                        new: dave
+
+            - name: Set sudo key
+              pallet_sudo:
+                set_key:
+                  from: alice
+                  new: bob
+        "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn frame_system() {
+        run_tool_spec(
+            r#"
+            - name: Remark
+              frame_system:
+                remark:
+                  from: alice
+                  remark: "0x010203"
+
+            - name: Storage manipulation
+              pallet_sudo:
+                sudo:
+                  from: alice
+                  call:
+                    frame_system:
+                      set_storage:
+                        items:
+                          - { key: "0xaabb", value: "0x01" }
+
+            - name: Storage removal
+              pallet_sudo:
+                sudo:
+                  from: alice
+                  call: "{{ item }}"
+              loop:
+                - frame_system:
+                    kill_storage:
+                      keys: ["0xaabb"]
+                - frame_system:
+                    kill_prefix:
+                      prefix: "0xaa"
+                      subkeys: 1
+                - frame_system:
+                    set_heap_pages:
+                      pages: 64
+
+            - name: Runtime upgrade
+              pallet_sudo:
+                sudo:
+                  from: alice
+                  call:
+                    frame_system:
+                      set_code: {}
+        "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn decode() {
+        run_tool_spec(
+            r#"
+            - name: Decode digest item
+              decode:
+                digest_item:
+                  data: "0x000402"
+        "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn readable_output() {
+        run_tool_spec_with_output(
+            r#"
+            - name: Readable transfer
+              pallet_balances:
+                transfer:
+                  from: alice
+                  to: bob
+                  balance: 100
+        "#,
+            OutputMode::Readable,
+            None,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn when_conditions() {
+        run_tool_spec(
+            r#"
+            - name: Transfers
+              pallet_balances:
+                transfer:
+                  from: alice
+                  to: "{{ item }}"
+                  balance: 100
+              loop:
+                - bob
+                - dave
+              register: transfers
+
+            - name: Skipped transfer
+              pallet_balances:
+                transfer:
+                  from: alice
+                  to: bob
+                  balance: 100
+              when: transfers | length > 2
+              register: skipped
+
+            - name: Block with transfers
+              block:
+                build:
+                  header:
+                    digest:
+                      logs: []
+                  extrinsics: "{{ transfers }}"
+              when: skipped | length == 0 and transfers is defined
+        "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn templates() {
+        run_tool_spec(
+            r#"
+            - vars:
+                amount: 2
+                unit: DOT
+            - name: Transfers
+              pallet_balances:
+                transfer:
+                  from: "{{ sender | default('alice') }}"
+                  to: "{{ item }}"
+                  balance: "{{ amount * 3 + 1 }} {{ unit }}"
+              loop:
+                - bob
+                - dave
+              register: transfers
+
+            - name: Block with transfers
+              block:
+                build:
+                  header:
+                    digest:
+                      logs: []
+                  extrinsics: "{{ transfers + [] }}"
+              when: transfers | length == 2
+        "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn include_tasks() {
+        let temp_dir = TempDir::new("include");
+        let dir = temp_dir.path();
+        fs::create_dir_all(dir.join("workflows")).unwrap();
+
+        fs::write(
+            dir.join("workflows/funding.yml"),
+            r#"
+            - vars:
+                amount: 100
+            - name: Fund accounts
+              pallet_balances:
+                transfer:
+                  from: alice
+                  to: "{{ item }}"
+                  balance: "{{ amount }}"
+              loop:
+                - bob
+                - dave
+              register: funding
+        "#,
+        )
+        .unwrap();
+
+        let path = dir.join("spec.yml");
+        fs::write(
+            &path,
+            r#"
+            - import: workflows/funding.yml
+              vars:
+                amount: 1000
+            - name: Block with funding
+              block:
+                build:
+                  header:
+                    digest:
+                      logs: []
+                  extrinsics: "{{ funding }}"
+        "#,
+        )
+        .unwrap();
+
+        run_tool_spec_file(&path, OutputMode::Encoded, None).unwrap();
+    }
+
     #[test]
     fn genesis() {
         run_tool_spec(
@@ -68,4 +487,108 @@ mod tests {
         )
         .unwrap()
     }
+
+    #[test]
+    fn genesis_file() {
+        let dir = TempDir::new("genesis_file");
+        let path = dir.path().join("genesis.json");
+
+        let spec = run_call::<GenesisCmd>(
+            &mut Session::new(),
+            "custom: { accounts: [alice], aura_authorities: [bob], format: raw }",
+        )
+        .unwrap();
+        fs::write(&path, serde_json::to_string_pretty(&spec).unwrap()).unwrap();
+
+        let genesis_hash = Session::with_genesis(SpecChainSpec::try_from(spec.clone()).unwrap())
+            .client(None)
+            .unwrap()
+            .block_hash(0)
+            .unwrap();
+        let check_genesis = |session: &mut Session| {
+            let hash = session.client(None).unwrap().block_hash(0).unwrap();
+            assert_eq!(hash, genesis_hash);
+        };
+
+        // The genesis file of a single call.
+        let mut session = Session::new();
+        run_call::<BlockCmd>(
+            &mut session,
+            &format!(
+                "build_chain: {{ genesis_file: \"{}\", count: 1 }}",
+                path.display()
+            ),
+        )
+        .unwrap();
+        check_genesis(&mut session);
+
+        // The genesis of the whole session.
+        let mut session = Session::with_genesis(SpecChainSpec::from_file(&path).unwrap());
+        build_block_in(
+            &mut session,
+            "build: { header: { digest: { logs: [] } }, extrinsics: [] }",
+        )
+        .unwrap();
+        check_genesis(&mut session);
+
+        run_tool_spec(&format!(
+            r#"
+            - genesis_file: "{}"
+            - name: Build block
+              block:
+                build_chain:
+                  count: 1
+        "#,
+            path.display()
+        ))
+        .unwrap();
+
+        // Relative to the spec file.
+        let spec_path = dir.path().join("spec.yml");
+        fs::write(
+            &spec_path,
+            r#"
+            - genesis_file: genesis.json
+            - name: Build block
+              block:
+                build_chain:
+                  count: 1
+        "#,
+        )
+        .unwrap();
+        run_tool_spec_file(&spec_path, OutputMode::Encoded, None).unwrap();
+
+        // Either inline or as file, not both.
+        assert!(run_call::<BlockCmd>(
+            &mut Session::new(),
+            &format!(
+                "build_chain: {{ genesis: {}, genesis_file: \"{}\", count: 1 }}",
+                serde_json::to_string(&spec).unwrap(),
+                path.display()
+            ),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn genesis_custom() {
+        run_tool_spec(
+            r#"
+            - name: Create genesis
+              genesis:
+                custom:
+                  accounts:
+                    - alice
+                  balances:
+                    - account: "5FHneW46xGXgs5mUiveU4sbTyGBzmstUspZC92UhjJM694ty"
+                      balance: "1.5 KSM"
+                  sudo: alice
+                  grandpa_authorities:
+                    - account: alice
+                      weight: 10
+                  chain_type: live
+        "#,
+        )
+        .unwrap()
+    }
 }
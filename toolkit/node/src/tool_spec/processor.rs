@@ -1,12 +1,18 @@
-use crate::builder::{Builder, FunctionName, ModuleInfo, ModuleName};
+use super::expression::{self, Lookup, PathSegment};
+use super::{functions, template};
+use crate::builder::{format_output, Builder, FunctionName, ModuleInfo, ModuleName, OutputMode};
+use crate::executor::Session;
+use crate::primitives::SpecChainSpec;
 use crate::Result;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use std::cell::Cell;
 use std::cmp::PartialEq;
 use std::collections::HashMap;
+use std::fs;
 use std::hash::Hash;
 use std::mem::{drop, take};
+use std::path::{Path, PathBuf};
 
 pub trait Mapper: Sized + Eq + PartialEq + Hash {
     fn map(proc: &mut Processor<Self>, task: Task<Self>) -> Result<()>;
@@ -15,6 +21,9 @@ pub trait Mapper: Sized + Eq + PartialEq + Hash {
 pub struct Processor<TaskType: Eq + Hash> {
     global_var_pool: VarPool,
     tasks: Vec<Task<TaskType>>,
+    // The in-memory chain shared by all tasks.
+    session: Session,
+    output_mode: OutputMode,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -28,13 +37,40 @@ pub struct TaskOutcome<Data> {
 
 impl<TaskType: Eq + PartialEq + Hash + Clone + DeserializeOwned + Mapper> Processor<TaskType> {
     pub fn new(input: &str) -> Result<Self> {
-        let (global_var_pool, tasks) = global_parser::<TaskType>(input)?;
+        Self::with_base(input, Path::new(""))
+    }
+    // Included files and the genesis file are relative to the spec file.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        Self::with_base(
+            &fs::read_to_string(path)?,
+            path.parent().unwrap_or(Path::new("")),
+        )
+    }
+    fn with_base(input: &str, base: &Path) -> Result<Self> {
+        let (global_var_pool, tasks, genesis_file) = global_parser::<TaskType>(input, base)?;
+
+        let session = match genesis_file {
+            Some(path) => Session::with_genesis(SpecChainSpec::from_file(&base.join(path))?),
+            None => Session::new(),
+        };
 
         Ok(Processor {
             global_var_pool: global_var_pool,
             tasks: tasks,
+            session: session,
+            output_mode: OutputMode::default(),
         })
     }
+    // Overwrites the genesis of the session chain, including the one set in
+    // the spec.
+    pub fn with_genesis(mut self, genesis: SpecChainSpec) -> Self {
+        self.session = Session::with_genesis(genesis);
+        self
+    }
+    pub fn with_output_mode(mut self, mode: OutputMode) -> Self {
+        self.output_mode = mode;
+        self
+    }
     pub fn process(mut self) -> Result<()> {
         for task in take(&mut self.tasks) {
             TaskType::map(&mut self, task)?;
@@ -61,7 +97,7 @@ impl<TaskType: Eq + PartialEq + Hash + Clone + DeserializeOwned + Mapper> Proces
             module_name = Some(task.module_name());
             function_name = Some(task.function_name());
 
-            results.push(Command::from(task).run()?);
+            results.push(Command::from(task).run(&mut self.session)?);
         }
 
         if let Some(var_name) = register {
@@ -69,13 +105,21 @@ impl<TaskType: Eq + PartialEq + Hash + Clone + DeserializeOwned + Mapper> Proces
                 .insert_named(var_name, serde_yaml::to_value(results.clone())?);
         }
 
+        // Every iteration was skipped by the `when:` condition.
+        if results.is_empty() {
+            return Ok(());
+        }
+
         println!(
             "{}",
             serde_json::to_string_pretty(&TaskOutcome {
                 task_name: Some(task.name().to_string()),
                 module: module_name.unwrap(),
                 function: function_name.unwrap(),
-                data: results,
+                data: results
+                    .into_iter()
+                    .map(|result| format_output(result, self.output_mode))
+                    .collect::<Result<Vec<serde_json::Value>>>()?,
             })?
         );
 
@@ -86,14 +130,18 @@ impl<TaskType: Eq + PartialEq + Hash + Clone + DeserializeOwned + Mapper> Proces
 // The `global_parser` parses tasks and global variables and inserts those
 // variables into the global variable pool. It does however not "expand" the
 // tasks (such as recurring tasks which have loops, or having to insert
-// variables). That job is done by the `task_parser`.
+// variables). That job is done by the `task_parser`. Additionally, it returns
+// the path of the genesis file the session chain is started with, if any.
+// Included files are resolved relative to the `base` directory.
 fn global_parser<TaskType: Eq + PartialEq + Hash + DeserializeOwned>(
     input: &str,
-) -> Result<(VarPool, Vec<Task<TaskType>>)> {
+    base: &Path,
+) -> Result<(VarPool, Vec<Task<TaskType>>, Option<PathBuf>)> {
     let yaml_blocks: Vec<YamlItem<TaskType>> = serde_yaml::from_str(input)?;
 
     let mut tasks = vec![];
     let mut global_vars = None;
+    let mut genesis_file = None;
     let mut global_var_pool = VarPool::new();
 
     // A "local" variable pool is not relevant in this context.
@@ -117,6 +165,18 @@ fn global_parser<TaskType: Eq + PartialEq + Hash + DeserializeOwned>(
                     ));
                 }
             }
+            YamlItem::GenesisFile(setting) => {
+                if genesis_file.is_none() {
+                    genesis_file = Some(setting.genesis_file);
+                } else {
+                    return Err(failure::err_msg("Only one genesis file entry allowed"));
+                }
+            }
+            YamlItem::Include(include) => tasks.extend(include_tasks(
+                &base.join(include.include_tasks),
+                include.vars,
+                &mut vec![],
+            )?),
             YamlItem::Task(task) => tasks.push(task),
         }
     }
@@ -129,7 +189,82 @@ fn global_parser<TaskType: Eq + PartialEq + Hash + DeserializeOwned>(
         global_var_pool.insert(vars);
     }
 
-    Ok((global_var_pool, tasks))
+    Ok((global_var_pool, tasks, genesis_file))
+}
+
+// Parses the tasks of an included file. The variables of the file and the ones
+// passed to the include are added to the variables of each task, where the
+// variables of the task itself take precedence. Those variables are processed
+// along with the task, so they can refer to global variables. `stack` contains
+// the files currently being included, in order to detect recursion.
+fn include_tasks<TaskType: Eq + PartialEq + Hash + DeserializeOwned>(
+    path: &Path,
+    vars: VarType,
+    stack: &mut Vec<PathBuf>,
+) -> Result<Vec<Task<TaskType>>> {
+    let path = path.canonicalize().map_err(|err| {
+        failure::err_msg(format!("Failed to include {}: {}", path.display(), err))
+    })?;
+
+    if stack.contains(&path) {
+        return Err(failure::err_msg(format!(
+            "Recursive include of {}",
+            path.display()
+        )));
+    }
+
+    let yaml_blocks: Vec<YamlItem<TaskType>> = serde_yaml::from_str(&fs::read_to_string(&path)?)?;
+    let base = path.parent().unwrap_or(Path::new("")).to_path_buf();
+
+    // The variables of the file are overwritten by the passed variables.
+    let mut scope = None;
+    for item in &yaml_blocks {
+        if let YamlItem::Vars(file_vars) = item {
+            if scope.is_none() {
+                scope = Some(file_vars.vars.clone());
+            } else {
+                return Err(failure::err_msg(
+                    "Only one global variable entry block allowed",
+                ));
+            }
+        }
+    }
+
+    let mut scope = scope.unwrap_or_default();
+    scope.0.extend(vars.0);
+
+    stack.push(path.clone());
+
+    let mut tasks = vec![];
+    for item in yaml_blocks {
+        match item {
+            YamlItem::Task(mut task) => {
+                task.insert_vars(&scope)?;
+                tasks.push(task);
+            }
+            YamlItem::Include(include) => {
+                let mut vars = scope.clone();
+                vars.0.extend(include.vars.0);
+
+                tasks.extend(include_tasks(
+                    &base.join(include.include_tasks),
+                    vars,
+                    stack,
+                )?);
+            }
+            YamlItem::Vars(_) => {}
+            YamlItem::GenesisFile(_) => {
+                return Err(failure::err_msg(format!(
+                    "Genesis file entries are not allowed in included files: {}",
+                    path.display()
+                )))
+            }
+        }
+    }
+
+    stack.pop();
+
+    Ok(tasks)
 }
 
 // The `task_parser` "expands" each tasks, such as creating a new tasks for each
@@ -149,6 +284,7 @@ fn task_parser<
 
     let mut vars = None;
     let mut loop_vars = None;
+    let mut when = None;
 
     // First, just collect the necessary information in order to be able to
     // expand the tasks. This includes variables, loops and any special
@@ -174,6 +310,7 @@ fn task_parser<
                         return Err(failure::err_msg("Only one loop entry per task allowed"));
                     }
                 }
+                Keyword::When => when = Some(val.clone()),
                 Keyword::Vars => {
                     // Ensure only one `vars:` entry is present per task.
                     if vars.is_none() {
@@ -213,9 +350,17 @@ fn task_parser<
 
     // Expand all tasks, where variables and loops are all layed out.
     for index in 0..loop_count {
-        let mut loop_properties = properties.clone();
         // Created an new variable processor with the new `index`.
         let converter = VariableProcessor::new(global_var_pool, &local_var_pool, index);
+
+        // Skip the iteration if its condition does not hold.
+        if let Some(when) = &when {
+            if !converter.evaluate_condition(when)? {
+                continue;
+            }
+        }
+
+        let mut loop_properties = properties.clone();
         converter.process_properties(&mut loop_properties)?;
 
         for (key, val) in loop_properties {
@@ -258,24 +403,10 @@ impl<'a> VariableProcessor<'a> {
     }
     fn process_yaml_value(&self, value: &mut serde_yaml::Value) -> Result<()> {
         if let Some(string) = value.as_str() {
-            // Check whether the value is a variable. If not, then just ignore.
-            if let Some(var_chain) = VariableChain::new(string)? {
-                // Check the local variable pool first (local pool overwrites
-                // the global pool). If nothing was found, then check to global
-                // variable pool.
-                let var = if let Some(var) = self.local_var_pool.get(self.loop_index, &var_chain) {
-                    var
-                } else if let Some(var) = self.global_var_pool.get(self.loop_index, &var_chain) {
-                    var
-                } else {
-                    return Err(failure::err_msg(format!(
-                        "Variable \"{}\" not found",
-                        string
-                    )));
-                };
-
-                // Overwrite the variable with the actual value.
-                *value = var.clone();
+            // Check whether the value is a template. If not, then just ignore.
+            if let Some(rendered) = template::render(string, self)? {
+                // Overwrite the template with the rendered value.
+                *value = rendered;
                 // Process the actual value; it might contain variables itself.
                 self.process_yaml_value(value)?;
             }
@@ -291,6 +422,42 @@ impl<'a> VariableProcessor<'a> {
 
         Ok(())
     }
+    // Evaluates the `when:` condition, which is either a boolean, an
+    // expression or a list of expressions which must all hold.
+    fn evaluate_condition(&self, when: &serde_yaml::Value) -> Result<bool> {
+        match when {
+            serde_yaml::Value::Bool(boolean) => Ok(*boolean),
+            serde_yaml::Value::String(expr) => expression::evaluate_condition(expr, self),
+            serde_yaml::Value::Sequence(conditions) => {
+                for condition in conditions {
+                    if !self.evaluate_condition(condition)? {
+                        return Ok(false);
+                    }
+                }
+
+                Ok(true)
+            }
+            _ => Err(failure::err_msg(format!(
+                "Invalid condition, expected an expression: {:?}",
+                when
+            ))),
+        }
+    }
+}
+
+impl<'a> Lookup for VariableProcessor<'a> {
+    fn lookup(&self, path: &[PathSegment]) -> Option<serde_yaml::Value> {
+        let var_chain = VariableChain::from_path(path)?;
+
+        // The local variable pool overwrites the global pool.
+        self.local_var_pool
+            .get(self.loop_index, &var_chain)
+            .or_else(|| self.global_var_pool.get(self.loop_index, &var_chain))
+            .cloned()
+    }
+    fn call(&self, name: &str, args: Vec<serde_yaml::Value>) -> Result<serde_yaml::Value> {
+        functions::call(name, args)
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -307,63 +474,26 @@ enum VariableType {
     Index(usize),
 }
 
-// TODO: Variable cannot start with an index.
 impl VariableChain {
-    fn new(name: &str) -> Result<Option<Self>> {
-        // Check if input qualifies as a variable
-        let name = if name.contains("{{") && name.contains("}}") {
-            name.replace("{{", "").replace("}}", "").trim().to_string()
-        } else {
-            return Ok(None);
+    // Creates the chain from the variable path of an expression. Returns
+    // `None` if the path starts with an index.
+    fn from_path(path: &[PathSegment]) -> Option<Self> {
+        let is_loop = match path.first()? {
+            PathSegment::Name(name) => name == "item",
+            PathSegment::Index(_) => return None,
         };
 
-        // Process variable
-        let mut chain = vec![];
-        let mut is_loop = false;
-        let parts: Vec<&str> = name.split('.').collect();
-
-        for part in parts {
-            let subparts: Vec<&str> = part.split('[').collect();
-
-            let mut first = true;
-            for sub in subparts {
-                // First item is a regular name.
-                if first {
-                    // Handle special case of "item", which is reserved for loop iterations.
-                    if sub == "item" {
-                        is_loop = true;
-                    }
-
-                    chain.push(VariableType::Name(sub.into()));
-                    first = false;
-                    continue;
-                }
-
-                // "item" is not allowed to have indexes.
-                if is_loop {
-                    return Err(failure::err_msg(
-                        "'item' is a reserved keyword and is not \
-                    allowed how have array indexes",
-                    ));
-                }
-
-                // Any other items are (array) indexes.
-                chain.push(VariableType::Index(
-                    sub.trim_matches(']').parse::<usize>().map_err(|_| {
-                        failure::err_msg(format!(
-                            "Expected a number as index in variable: {}",
-                            part
-                        ))
-                    })?,
-                ));
-            }
-        }
-
-        Ok(Some(VariableChain {
-            chain: chain,
+        Some(VariableChain {
+            chain: path
+                .iter()
+                .map(|segment| match segment {
+                    PathSegment::Name(name) => VariableType::Name(name.as_str().into()),
+                    PathSegment::Index(index) => VariableType::Index(*index),
+                })
+                .collect(),
             is_loop: is_loop,
             cursor: Cell::new(0),
-        }))
+        })
     }
     // Fetches the current variable. This call advances the cursor.
     fn get(&self) -> Option<&VariableType> {
@@ -423,7 +553,7 @@ impl VarPool {
                     self.pool.0.get(var)
                 }
             }
-            // Should never occur, since this case is handled in the `VariableChain::new()`
+            // Should never occur, since this case is handled in `VariableChain::from_path()`
             VariableType::Index(_) => {
                 panic!("Variable name starts with an index: {:?}", name.chain)
             }
@@ -454,7 +584,10 @@ impl VarPool {
 #[serde(untagged)]
 enum YamlItem<TaskType: Eq + PartialEq + Hash> {
     Task(Task<TaskType>),
+    // Must precede `Vars`, since includes can contain variables.
+    Include(Include),
     Vars(Vars),
+    GenesisFile(GenesisFile),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -462,6 +595,21 @@ struct Vars {
     vars: VarType,
 }
 
+// Inserts the tasks of another spec file, with the given variables.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Include {
+    #[serde(alias = "import")]
+    include_tasks: PathBuf,
+    #[serde(default)]
+    vars: VarType,
+}
+
+// Starts the session chain on top of the given chain spec file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct GenesisFile {
+    genesis_file: PathBuf,
+}
+
 #[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
 struct VarType(HashMap<VariableName, serde_yaml::Value>);
 
@@ -514,6 +662,29 @@ impl<TaskType: Eq + PartialEq + Hash> Task<TaskType> {
 
         Ok(task.ok_or(failure::err_msg("No task found for yaml block"))?)
     }
+    // Adds the variables to the task, unless the task specifies them itself.
+    fn insert_vars(&mut self, vars: &VarType) -> Result<()> {
+        if vars.0.is_empty() {
+            return Ok(());
+        }
+
+        let entry = self
+            .properties
+            .entry(KeyType::Keyword(Keyword::Vars))
+            .or_insert(serde_yaml::Value::Mapping(Default::default()));
+
+        let mut task_vars = serde_yaml::from_value::<VarType>(entry.clone())?;
+        for (name, value) in &vars.0 {
+            task_vars
+                .0
+                .entry(name.clone())
+                .or_insert_with(|| value.clone());
+        }
+
+        *entry = serde_yaml::to_value(task_vars)?;
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
@@ -531,12 +702,19 @@ enum Keyword {
     Loop,
     #[serde(rename = "vars")]
     Vars,
+    #[serde(rename = "when")]
+    When,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::builder::test_utils::TempDir;
+    use crate::primitives::storage_prefix;
+    use crate::tool_spec::Mapping;
+    use codec::Encode;
     use serde::de::DeserializeOwned;
+    use sp_runtime::traits::Header as HeaderT;
 
     #[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
     #[serde(rename_all = "snake_case")]
@@ -546,34 +724,49 @@ mod tests {
 
     /// Convenience function for processing tests.
     fn parse<T: DeserializeOwned>(input: &str) -> Vec<T> {
-        let (var_pool, mut tasks) = global_parser::<TaskType>(input).unwrap();
+        let (var_pool, mut tasks, _) = global_parser::<TaskType>(input, Path::new("")).unwrap();
         task_parser::<TaskType, T>(&var_pool, &mut tasks[0].properties)
             .unwrap()
             .0
     }
 
+    struct TestVars;
+
+    impl Lookup for TestVars {
+        fn lookup(&self, _: &[PathSegment]) -> Option<serde_yaml::Value> {
+            None
+        }
+    }
+
+    // Parses the variable of the template into a chain.
+    fn chain(template: &str) -> Vec<VariableType> {
+        let expr = template.trim_start_matches("{{").trim_end_matches("}}");
+        match expression::parse(expr).unwrap() {
+            expression::Expr::Variable(path) => VariableChain::from_path(&path).unwrap().chain,
+            other => panic!("Not a variable: {:?}", other),
+        }
+    }
+
     #[test]
     fn nested_variables_simple_names() {
-        let res = VariableChain::new("var").unwrap();
+        let res = template::render("var", &TestVars).unwrap();
         assert!(res.is_none());
 
-        let res = VariableChain::new("{{ var }}").unwrap().unwrap();
-        assert_eq!(res.chain, vec![VariableType::Name("var".into())]);
+        let res = chain("{{ var }}");
+        assert_eq!(res, vec![VariableType::Name("var".into())]);
 
-        let res = VariableChain::new("{{ var.name }}").unwrap().unwrap();
+        let res = chain("{{ var.name }}");
         assert_eq!(
-            res.chain,
+            res,
             vec![
                 VariableType::Name("var".into()),
                 VariableType::Name("name".into())
             ]
         );
 
-        let res = VariableChain::new("{{ var.name.surname }}")
-            .unwrap()
-            .unwrap();
+        let res = chain("{{ var.name.surname }}");
         assert_eq!(
-            res.chain,
+            res,
             vec![
                 VariableType::Name("var".into()),
                 VariableType::Name("name".into()),
@@ -584,9 +777,9 @@ mod tests {
 
     #[test]
     fn nested_variables_indexes() {
-        let res = VariableChain::new("{{ var.name[0] }}").unwrap().unwrap();
+        let res = chain("{{ var.name[0] }}");
         assert_eq!(
-            res.chain,
+            res,
             vec![
                 VariableType::Name("var".into()),
                 VariableType::Name("name".into()),
@@ -594,11 +787,9 @@ mod tests {
             ]
         );
 
-        let res = VariableChain::new("{{ var.name[0].surname }}")
-            .unwrap()
-            .unwrap();
+        let res = chain("{{ var.name[0].surname }}");
         assert_eq!(
-            res.chain,
+            res,
             vec![
                 VariableType::Name("var".into()),
                 VariableType::Name("name".into()),
@@ -607,9 +798,9 @@ mod tests {
             ]
         );
 
-        let res = VariableChain::new("{{ var.name[0][4] }}").unwrap().unwrap();
+        let res = chain("{{ var.name[0][4] }}");
         assert_eq!(
-            res.chain,
+            res,
             vec![
                 VariableType::Name("var".into()),
                 VariableType::Name("name".into()),
@@ -618,11 +809,9 @@ mod tests {
             ]
         );
 
-        let res = VariableChain::new("{{ var.name[0][4].categories.parts[1][1] }}")
-            .unwrap()
-            .unwrap();
+        let res = chain("{{ var.name[0][4].categories.parts[1][1] }}");
         assert_eq!(
-            res.chain,
+            res,
             vec![
                 VariableType::Name("var".into()),
                 VariableType::Name("name".into()),
@@ -1059,4 +1248,259 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn converter_when() {
+        #[derive(Debug, Eq, PartialEq, Deserialize)]
+        struct Person {
+            name: String,
+            age: usize,
+        }
+
+        let yaml = r#"
+            - vars:
+                min_age: 30
+            - name: Some persons
+              person:
+                name: "{{ item.name }}"
+                age: "{{ item.age }}"
+              loop:
+                - { name: alice, age: 33 }
+                - { name: bob, age: 25 }
+                - { name: eve, age: 40 }
+              when: item.age >= min_age and item.name != 'eve'
+        "#;
+
+        let res = parse::<Person>(yaml);
+        assert_eq!(
+            res,
+            vec![Person {
+                name: "alice".to_string(),
+                age: 33,
+            }]
+        );
+
+        // Every condition of a list must hold.
+        let yaml = r#"
+            - name: Some person
+              person:
+                name: alice
+                age: "{{ age }}"
+              vars:
+                age: 33
+              when:
+                - age is defined
+                - missing is not defined
+                - age > 40
+        "#;
+
+        let res = parse::<Person>(yaml);
+        assert!(res.is_empty());
+
+        // Variables of skipped iterations are not required to exist.
+        let yaml = r#"
+            - name: Some person
+              person:
+                name: alice
+                age: "{{ missing }}"
+              when: false
+        "#;
+
+        let res = parse::<Person>(yaml);
+        assert!(res.is_empty());
+    }
+
+    #[test]
+    fn converter_include() {
+        #[derive(Debug, Eq, PartialEq, Deserialize)]
+        struct Person {
+            name: String,
+            age: usize,
+        }
+
+        let temp_dir = TempDir::new("include_tasks");
+        let dir = temp_dir.path();
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        fs::write(
+            dir.join("lib/persons.yml"),
+            r#"
+            - vars:
+                name: alice
+                age: 33
+            - name: Person from include
+              person:
+                name: "{{ name }}"
+                age: "{{ age }}"
+            - name: Person with own vars
+              person:
+                name: "{{ name }}"
+                age: "{{ age }}"
+              vars:
+                age: 40
+            - include_tasks: other.yml
+              vars:
+                name: eve
+        "#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("lib/other.yml"),
+            r#"
+            - name: Nested person
+              person:
+                name: "{{ name }}"
+                age: "{{ default_age }}"
+        "#,
+        )
+        .unwrap();
+
+        let yaml = r#"
+            - vars:
+                default_age: 20
+            - import: lib/persons.yml
+              vars:
+                name: bob
+        "#;
+
+        let (var_pool, tasks, _) = global_parser::<TaskType>(yaml, dir).unwrap();
+        let res: Vec<Person> = tasks
+            .iter()
+            .flat_map(|task| {
+                task_parser::<TaskType, Person>(&var_pool, &task.properties)
+                    .unwrap()
+                    .0
+            })
+            .collect();
+
+        assert_eq!(
+            res,
+            vec![
+                Person {
+                    name: "bob".to_string(),
+                    age: 33,
+                },
+                Person {
+                    name: "bob".to_string(),
+                    age: 40,
+                },
+                Person {
+                    name: "eve".to_string(),
+                    age: 20,
+                },
+            ]
+        );
+
+        // Files including each other.
+        fs::write(
+            dir.join("lib/recursive.yml"),
+            "- include_tasks: recursive.yml",
+        )
+        .unwrap();
+        let yaml = "- include_tasks: lib/recursive.yml";
+        assert!(global_parser::<TaskType>(yaml, dir).is_err());
+
+        let yaml = "- include_tasks: lib/missing.yml";
+        assert!(global_parser::<TaskType>(yaml, dir).is_err());
+    }
+
+    #[test]
+    fn converter_templates() {
+        #[derive(Debug, Eq, PartialEq, Deserialize)]
+        struct Person {
+            name: String,
+            age: usize,
+            categories: Vec<String>,
+        }
+
+        let yaml = r#"
+            - vars:
+                suffix: stash
+                base_age: 30
+            - name: Some persons
+              person:
+                name: "//{{ item[0] | default('unknown') }}//{{ suffix }}"
+                age: "{{ base_age + item[1] }}"
+                categories: "{{ item[2] | default(['none']) }}"
+              loop:
+                - [alice, 3]
+                - [bob, 5]
+        "#;
+
+        let res = parse::<Person>(yaml);
+        assert_eq!(
+            res,
+            vec![
+                Person {
+                    name: "//alice//stash".to_string(),
+                    age: 33,
+                    categories: vec!["none".to_string()],
+                },
+                Person {
+                    name: "//bob//stash".to_string(),
+                    age: 35,
+                    categories: vec!["none".to_string()],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn template_functions() {
+        let mut proc = Processor::<Mapping>::new(
+            r#"
+            - name: Genesis with timestamp
+              genesis:
+                default:
+                  storage:
+                    - key: "{{ storage_key('Timestamp', 'Now') }}"
+                      value: "{{ scale_encode('u64', 1000) }}"
+              register: genesis
+
+            - name: First block
+              block:
+                build:
+                  genesis: "{{ genesis[0] }}"
+                  header:
+                    digest:
+                      logs: []
+                  extrinsics: []
+              register: first
+
+            - name: Second block
+              block:
+                build:
+                  header:
+                    parent_hash: "{{ block_hash(first[0]) }}"
+                    digest:
+                      logs: []
+                  extrinsics: []
+              when: ss58(account_id('alice')) == ss58('alice')
+        "#,
+        )
+        .unwrap();
+
+        for task in take(&mut proc.tasks) {
+            Mapping::map(&mut proc, task).unwrap();
+        }
+
+        // The session chain was started on the overridden genesis.
+        let client = proc.session.client(None).unwrap();
+        let genesis_hash = client.block_hash(0).unwrap();
+        assert_eq!(
+            client
+                .storage(genesis_hash, &storage_prefix("Timestamp", "Now"))
+                .unwrap(),
+            Some(1000u64.encode())
+        );
+
+        let first = client.header(1).unwrap();
+        assert_eq!(client.header(2).unwrap().parent_hash, first.hash());
+
+        let local = VarPool::new();
+        let vars = VariableProcessor::new(&proc.global_var_pool, &local, 0);
+        assert_eq!(
+            expression::evaluate("block_hash(first[0])", &vars).unwrap(),
+            serde_yaml::Value::String(format!("{:?}", first.hash()))
+        );
+    }
 }
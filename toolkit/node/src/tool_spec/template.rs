@@ -0,0 +1,150 @@
+use super::expression::{self, Lookup};
+use crate::Result;
+use serde_yaml::Value;
+
+// Templates embed expressions into strings, such as `//{{ name }}//stash`. A
+// template consisting of a single expression, such as `{{ balance * 2 }}`, is
+// replaced by the value of that expression and keeps its type. Otherwise, the
+// values are inserted as text into the string.
+
+enum Segment<'a> {
+    Text(&'a str),
+    Expr(&'a str),
+}
+
+fn segments(template: &str) -> Result<Vec<Segment>> {
+    let mut segments = vec![];
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            segments.push(Segment::Text(&rest[..start]));
+        }
+
+        let end = rest[start..]
+            .find("}}")
+            .map(|end| start + end)
+            .ok_or(failure::err_msg(format!(
+                "Unterminated `{{{{` in template: {}",
+                template
+            )))?;
+
+        segments.push(Segment::Expr(rest[start + 2..end].trim()));
+        rest = &rest[end + 2..];
+    }
+
+    if !rest.is_empty() {
+        segments.push(Segment::Text(rest));
+    }
+
+    Ok(segments)
+}
+
+// Renders the template. Returns `None` if the string is not a template.
+pub fn render<L: Lookup>(template: &str, vars: &L) -> Result<Option<Value>> {
+    if !template.contains("{{") {
+        return Ok(None);
+    }
+
+    if let [Segment::Expr(expr)] = segments(template.trim())?.as_slice() {
+        return expression::evaluate(expr, vars).map(Some);
+    }
+
+    let mut rendered = String::new();
+    for segment in segments(template)? {
+        match segment {
+            Segment::Text(text) => rendered.push_str(text),
+            Segment::Expr(expr) => {
+                rendered.push_str(&expression::to_text(&expression::evaluate(expr, vars)?)?)
+            }
+        }
+    }
+
+    Ok(Some(Value::String(rendered)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tool_spec::expression::PathSegment;
+
+    struct Vars(Value);
+
+    impl Lookup for Vars {
+        fn lookup(&self, path: &[PathSegment]) -> Option<Value> {
+            let mut value = &self.0;
+            for segment in path {
+                value = match segment {
+                    PathSegment::Name(name) => value.get(name.as_str())?,
+                    PathSegment::Index(index) => value.get(*index)?,
+                };
+            }
+            Some(value.clone())
+        }
+    }
+
+    fn render_str(template: &str) -> Result<Option<Value>> {
+        let vars = Vars(
+            serde_yaml::from_str(
+                r#"
+                name: alice
+                balance: 100
+                enabled: true
+                accounts: [alice, bob]
+                info: { age: 33 }
+            "#,
+            )
+            .unwrap(),
+        );
+
+        render(template, &vars)
+    }
+
+    fn yaml(input: &str) -> Value {
+        serde_yaml::from_str(input).unwrap()
+    }
+
+    #[test]
+    fn whole_values_keep_type() {
+        assert_eq!(render_str("{{ balance }}").unwrap(), Some(yaml("100")));
+        assert_eq!(
+            render_str(" {{ balance * 2 }} ").unwrap(),
+            Some(yaml("200"))
+        );
+        assert_eq!(render_str("{{ enabled }}").unwrap(), Some(yaml("true")));
+        assert_eq!(
+            render_str("{{ accounts }}").unwrap(),
+            Some(yaml("[alice, bob]"))
+        );
+        assert_eq!(render_str("{{ info }}").unwrap(), Some(yaml("{ age: 33 }")));
+    }
+
+    #[test]
+    fn interpolation() {
+        assert_eq!(
+            render_str("//{{ name }}//stash").unwrap(),
+            Some(yaml("//alice//stash"))
+        );
+        assert_eq!(
+            render_str("{{ name }} has {{ balance + 1 }}").unwrap(),
+            Some(yaml("alice has 101"))
+        );
+        assert_eq!(
+            render_str("accounts: {{ accounts }}, age: {{ info.age }}").unwrap(),
+            Some(yaml(r#"'accounts: ["alice","bob"], age: 33'"#))
+        );
+        assert_eq!(
+            render_str("{{ accounts[1] }}{{ name | length }}").unwrap(),
+            Some(yaml("bob5"))
+        );
+    }
+
+    #[test]
+    fn no_template_or_invalid() {
+        assert_eq!(render_str("name").unwrap(), None);
+        assert_eq!(render_str("plain }} text").unwrap(), None);
+        assert!(render_str("{{ name").is_err());
+        assert!(render_str("//{{ missing }}").is_err());
+        assert!(render_str("{{ }}").is_err());
+    }
+}